@@ -0,0 +1,28 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec3 color;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.5, -1.0, -0.75));
+const float AMBIENT = 0.2;
+
+void main() {
+    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(position, 1.0);
+    vec3 worldNormal = normalize(mat3(transpose(inverse(ubo.model))) * normal);
+    float diffuse = max(dot(worldNormal, -LIGHT_DIRECTION), 0.0);
+    fragColor = color * (AMBIENT + (1.0 - AMBIENT) * diffuse);
+}
@@ -6,11 +6,17 @@ layout(location = 1) in vec3 color;
 
 layout(location = 0) out vec3 fragColor;
 
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
 out gl_PerVertex {
     vec4 gl_Position;
 };
 
 void main() {
-    gl_Position = vec4(pos, 0.0, 1.0);
+    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(pos, 0.0, 1.0);
     fragColor = color;
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use vulkano::format::Format;
+
+use crate::error::RendererError;
+
+/// A frame read back from the GPU, always stored as tightly packed RGBA8.
+pub struct FrameCapture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl FrameCapture {
+    /// Converts raw image memory in `format` to RGBA8.
+    pub fn from_raw(width: u32, height: u32, format: Format, data: &[u8]) -> Result<Self, RendererError> {
+        let pixels = match format {
+            Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => data.to_vec(),
+            Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => data.chunks_exact(4)
+                .flat_map(|bgra| vec![bgra[2], bgra[1], bgra[0], bgra[3]])
+                .collect(),
+            _ => return Err(RendererError::UnsupportedReadbackFormat(format)),
+        };
+
+        Ok(Self { width, height, pixels })
+    }
+
+    /// Loads an 8-bit RGBA PNG, e.g. a previously saved reference frame.
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, png::DecodingError> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let (info, mut reader) = decoder.read_info()?;
+        if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+            return Err(png::DecodingError::Other("expected an 8-bit RGBA image".into()));
+        }
+
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels)?;
+        Ok(Self { width: info.width, height: info.height, pixels })
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)
+    }
+}
@@ -0,0 +1,462 @@
+use std::convert::Infallible;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use vulkano::device::Features;
+use vulkano::swapchain::{PresentMode, SupportedPresentModes};
+
+use crate::gpu::GpuSelector;
+use crate::texture::{SamplerSettings, TextureColorSpace};
+use crate::validation::ValidationSettings;
+use crate::vertex::VertexLayout;
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+/// Largest window/offscreen size accepted, matching the common `maxImageDimension2D` limit
+const MAX_DIMENSION: u32 = 16384;
+/// Highest sample count Vulkan defines
+const MAX_MSAA_SAMPLES: u32 = 64;
+
+#[cfg(all(debug_assertions))]
+const ENABLE_VALIDATION_LAYERS: bool = true;
+#[cfg(not(debug_assertions))]
+const ENABLE_VALIDATION_LAYERS: bool = false;
+
+pub const USAGE: &str = "\
+Options:
+    --config <file>          Load settings from a TOML file, the other flags override it wherever they are given
+    --width <pixels>         Window or offscreen image width
+    --height <pixels>        Window or offscreen image height
+    --title <text>           Window title
+    --validation             Enable the Vulkan validation layers
+    --no-validation          Disable the Vulkan validation layers
+    --validation-severity <level>  Least severe validation message to log: error, warning, info or verbose
+    --validation-types <list>      Comma separated validation message types: general, validation, performance
+    --validation-max-repeats <n>   Stop logging a validation message after n repeats, 0 for no limit
+    --layer <name>           Also enable this instance layer, e.g. VK_LAYER_LUNARG_api_dump; can be repeated
+    --strict-validation      Enable the validation layers and fail frames that report validation errors
+    --present-mode <mode>    Preferred present mode: mailbox, immediate, fifo or fifo-relaxed
+    --clear-color <r,g,b,a>  Background color, each component between 0 and 1
+    --gpu <index|name>       Use the GPU with this index or whose name contains this text
+    --list-gpus              Print the available GPUs and whether they can be used, then exit
+    --capability-report <file>  Write the instance, layer, device and surface capabilities as JSON, then exit
+    --mesh <file>            Draw this .obj, .gltf or .glb model instead of the built-in triangle
+    --vertex-layout <name>   Vertex format and shader to draw with: position2-color, position3-color,
+                             position3-normal-color, position3-normal-uv or position3-normal-uv-tangent
+    --texture <file>         PNG or JPEG image sampled by the vertex layouts with texture coordinates,
+                             replaces the base color texture of a glTF model
+    --texture-color-space <space>  srgb for colors (default) or linear for data such as normal maps
+    --texture-filter <filter>      Magnification and minification filter: nearest or linear
+    --texture-address-mode <mode>  repeat, mirrored-repeat, clamp-to-edge or clamp-to-border
+    --texture-mipmap-filter <filter>  Filter between mip levels: nearest or linear (default)
+    --texture-lod-bias <bias>      Added to the selected mip level, positive values blur
+    --texture-max-lod <level>      Last mip level sampled, 0 disables mipmapping
+    --shader-dir <dir>       Load the shaders from this directory at runtime and reload them when they change, a
+                             precompiled <name>.spv is used instead of the GLSL file <name> when present
+    --msaa <samples>         Multisample anti-aliasing sample count, clamped to what the device supports (default 1)
+    --spin                   Turn the model in front of the camera, needs a 3D vertex layout
+    --headless               Render one offscreen frame to headless.png instead of opening a window
+    --help                   Print this message";
+
+/// Present mode to use when the surface supports it.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresentModePreference {
+    Mailbox,
+    Immediate,
+    Fifo,
+    FifoRelaxed,
+}
+
+impl PresentModePreference {
+    /// `None` if the surface does not support this mode.
+    pub fn select(self, available: SupportedPresentModes) -> Option<PresentMode> {
+        match self {
+            PresentModePreference::Mailbox if available.mailbox => Some(PresentMode::Mailbox),
+            PresentModePreference::Immediate if available.immediate => Some(PresentMode::Immediate),
+            PresentModePreference::Fifo if available.fifo => Some(PresentMode::Fifo),
+            PresentModePreference::FifoRelaxed if available.relaxed => Some(PresentMode::Relaxed),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for PresentModePreference {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mailbox" => Ok(PresentModePreference::Mailbox),
+            "immediate" => Ok(PresentModePreference::Immediate),
+            "fifo" => Ok(PresentModePreference::Fifo),
+            "fifo-relaxed" => Ok(PresentModePreference::FifoRelaxed),
+            _ => Err(ConfigError::InvalidValue {
+                option: "present-mode",
+                reason: format!("unknown present mode `{}`", s),
+            }),
+        }
+    }
+}
+
+/// Settings the renderer is created with.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    /// Size of the window, or of the offscreen image when running headless
+    pub width: u32,
+    pub height: u32,
+    pub window_title: String,
+    pub enable_validation_layers: bool,
+    pub validation: ValidationSettings,
+    /// Instance layers enabled in addition to the validation layer
+    pub extra_layers: Vec<String>,
+    /// Falls back to mailbox, immediate then FIFO when `None` or unsupported
+    pub present_mode: Option<PresentModePreference>,
+    pub clear_color: [f32; 4],
+    /// OBJ or glTF model to draw, the built-in triangle when `None`
+    pub mesh: Option<PathBuf>,
+    /// Attributes of the mesh uploaded to the GPU, missing ones are zero
+    pub vertex_layout: VertexLayout,
+    /// Drawn on the model when its vertex layout has texture coordinates. When `None`, a glTF mesh's base color
+    /// texture is used, otherwise white
+    pub texture: Option<PathBuf>,
+    pub texture_color_space: TextureColorSpace,
+    pub sampler: SamplerSettings,
+    /// Directory with the files of `src/assets/shaders`, compiled at runtime and watched for changes. A precompiled
+    /// `<name>.spv` takes precedence over the GLSL file `<name>`. The shaders built into the binary are used when `None`.
+    pub shader_dir: Option<PathBuf>,
+    /// Samples per pixel, 1 disables multisampling. Lowered to the highest count the device supports.
+    pub msaa_samples: u32,
+    /// Turn the model in front of the camera, only for 3D vertex layouts
+    pub spin: bool,
+    /// Picks the best ranked suitable device when `None`
+    pub gpu: Option<GpuSelector>,
+    /// Enabled on the logical device, devices lacking any of them are not suitable. Set by the application, the
+    /// renderer itself needs none.
+    #[serde(skip)]
+    pub required_features: Features,
+    pub headless: bool,
+    pub list_gpus: bool,
+    pub capability_report: Option<PathBuf>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            width: WIDTH,
+            height: HEIGHT,
+            window_title: "Vulkan".into(),
+            enable_validation_layers: ENABLE_VALIDATION_LAYERS,
+            validation: ValidationSettings::default(),
+            extra_layers: vec![],
+            present_mode: None,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            mesh: None,
+            vertex_layout: VertexLayout::default(),
+            texture: None,
+            texture_color_space: TextureColorSpace::default(),
+            sampler: SamplerSettings::default(),
+            shader_dir: None,
+            msaa_samples: 1,
+            spin: false,
+            gpu: None,
+            required_features: Features::none(),
+            headless: false,
+            list_gpus: false,
+            capability_report: None,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let config = Self::read_file(path.as_ref())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses the file without validating it, `from_args` validates once the flags are applied.
+    fn read_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_owned(), e))
+    }
+
+    /// Builds the configuration from command-line arguments, excluding the program name.
+    ///
+    /// Values from `--config` are applied first, the remaining flags override them whether they come before or after it.
+    /// The result is validated as a whole, so flags can fix values the file alone would be rejected for.
+    pub fn from_args<I>(args: I) -> Result<Self, ConfigError>
+        where I: IntoIterator<Item = String>
+    {
+        let args: Vec<String> = args.into_iter().collect();
+
+        let mut config = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Self::read_file(args.get(i + 1).ok_or(ConfigError::MissingValue("config"))?.as_ref())?,
+            None => Self::default(),
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |option: &'static str| args.next().ok_or(ConfigError::MissingValue(option));
+
+            match arg.as_str() {
+                "--config" => { value("config")?; }
+                "--width" => config.width = parse_number("width", &value("width")?)?,
+                "--height" => config.height = parse_number("height", &value("height")?)?,
+                "--title" => config.window_title = value("title")?,
+                "--validation" => config.enable_validation_layers = true,
+                "--no-validation" => config.enable_validation_layers = false,
+                "--validation-severity" => config.validation.min_severity = value("validation-severity")?.parse()?,
+                "--validation-types" => config.validation.set_message_types(&value("validation-types")?)?,
+                "--layer" => config.extra_layers.push(value("layer")?),
+                "--strict-validation" => {
+                    config.enable_validation_layers = true;
+                    config.validation.strict = true;
+                }
+                "--validation-max-repeats" =>
+                    config.validation.max_repeats = parse_number("validation-max-repeats", &value("validation-max-repeats")?)?,
+                "--present-mode" => config.present_mode = Some(value("present-mode")?.parse()?),
+                "--clear-color" => config.clear_color = parse_color(&value("clear-color")?)?,
+                "--gpu" => config.gpu = Some(value("gpu")?.parse()?),
+                "--mesh" => config.mesh = Some(value("mesh")?.into()),
+                "--vertex-layout" => config.vertex_layout = value("vertex-layout")?.parse()?,
+                "--texture" => config.texture = Some(value("texture")?.into()),
+                "--texture-color-space" => config.texture_color_space = value("texture-color-space")?.parse()?,
+                "--texture-filter" => {
+                    let filter = value("texture-filter")?.parse()?;
+                    config.sampler.mag_filter = filter;
+                    config.sampler.min_filter = filter;
+                }
+                "--texture-address-mode" => config.sampler.address_mode = value("texture-address-mode")?.parse()?,
+                "--texture-mipmap-filter" => config.sampler.mipmap_filter = value("texture-mipmap-filter")?.parse()?,
+                "--texture-lod-bias" => config.sampler.lod_bias = parse_float("texture-lod-bias", &value("texture-lod-bias")?)?,
+                "--texture-max-lod" => config.sampler.max_lod = parse_float("texture-max-lod", &value("texture-max-lod")?)?,
+                "--shader-dir" => config.shader_dir = Some(value("shader-dir")?.into()),
+                "--msaa" => config.msaa_samples = parse_number("msaa", &value("msaa")?)?,
+                "--spin" => config.spin = true,
+                "--headless" => config.headless = true,
+                "--list-gpus" => config.list_gpus = true,
+                "--capability-report" => config.capability_report = Some(value("capability-report")?.into()),
+                "--help" | "-h" => return Err(ConfigError::HelpRequested),
+                _ => return Err(ConfigError::UnknownArgument(arg)),
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for &(option, value) in &[("width", self.width), ("height", self.height)] {
+            if value == 0 || value > MAX_DIMENSION {
+                return Err(ConfigError::InvalidValue {
+                    option,
+                    reason: format!("{} is not between 1 and {}", value, MAX_DIMENSION),
+                });
+            }
+        }
+
+        if self.clear_color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(ConfigError::InvalidValue {
+                option: "clear-color",
+                reason: format!("{:?} has components outside of 0..1", self.clear_color),
+            });
+        }
+
+        if !self.msaa_samples.is_power_of_two() || self.msaa_samples > MAX_MSAA_SAMPLES {
+            return Err(ConfigError::InvalidValue {
+                option: "msaa",
+                reason: format!("{} is not a power of two between 1 and {}", self.msaa_samples, MAX_MSAA_SAMPLES),
+            });
+        }
+
+        if self.validation.strict && !self.enable_validation_layers {
+            return Err(ConfigError::InvalidValue {
+                option: "strict-validation",
+                reason: "strict validation needs the validation layers, which are disabled".to_owned(),
+            });
+        }
+
+        //The 2D triangle is clockwise in clip space, seen through the camera it turns counter-clockwise and is culled
+        if self.spin && self.vertex_layout == VertexLayout::Position2Color {
+            return Err(ConfigError::InvalidValue {
+                option: "spin",
+                reason: format!("the {} layout is drawn in clip space, spinning needs a 3D vertex layout",
+                    VertexLayout::Position2Color),
+            });
+        }
+
+        self.sampler.validate()
+    }
+}
+
+fn parse_number(option: &'static str, value: &str) -> Result<u32, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        option,
+        reason: format!("`{}` is not a positive integer", value),
+    })
+}
+
+fn parse_float(option: &'static str, value: &str) -> Result<f32, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        option,
+        reason: format!("`{}` is not a number", value),
+    })
+}
+
+fn parse_color(value: &str) -> Result<[f32; 4], ConfigError> {
+    let invalid = || ConfigError::InvalidValue {
+        option: "clear-color",
+        reason: format!("`{}` is not four comma separated numbers", value),
+    };
+
+    let components = value.split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match components.as_slice() {
+        &[r, g, b, a] => Ok([r, g, b, a]),
+        _ => Err(invalid()),
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    UnknownArgument(String),
+    MissingValue(&'static str),
+    InvalidValue { option: &'static str, reason: String },
+    /// `--help` was passed, the caller should print `USAGE`
+    HelpRequested,
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) => Some(e),
+            ConfigError::Parse(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(fmt, "failed to read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(fmt, "invalid config file {}: {}", path.display(), e),
+            ConfigError::UnknownArgument(arg) => write!(fmt, "unknown argument `{}`", arg),
+            ConfigError::MissingValue(option) => write!(fmt, "--{} requires a value", option),
+            ConfigError::InvalidValue { option, reason } => write!(fmt, "invalid --{}: {}", option, reason),
+            ConfigError::HelpRequested => write!(fmt, "{}", USAGE),
+        }
+    }
+}
+
+/// For values like `GpuSelector` that parse from any string.
+impl From<Infallible> for ConfigError {
+    fn from(err: Infallible) -> Self {
+        match err {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_file::TempFile;
+
+    fn args(args: &[&str]) -> Result<RendererConfig, ConfigError> {
+        RendererConfig::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn config_file(name: &str, contents: &str) -> TempFile {
+        TempFile::with_contents(name, "toml", contents)
+    }
+
+    #[test]
+    fn unknown_flags_and_missing_values_are_rejected() {
+        assert!(matches!(args(&["--frobnicate"]), Err(ConfigError::UnknownArgument(arg)) if arg == "--frobnicate"));
+        assert!(matches!(args(&["--width"]), Err(ConfigError::MissingValue("width"))));
+        assert!(matches!(args(&["--headless", "--config"]), Err(ConfigError::MissingValue("config"))));
+        assert!(matches!(args(&["--help"]), Err(ConfigError::HelpRequested)));
+    }
+
+    #[test]
+    fn strict_validation_needs_the_layers() {
+        let config = args(&["--no-validation", "--strict-validation"]).unwrap();
+        assert!(config.enable_validation_layers && config.validation.strict);
+        assert!(matches!(args(&["--strict-validation", "--no-validation"]),
+            Err(ConfigError::InvalidValue { option: "strict-validation", .. })));
+    }
+
+    #[test]
+    fn spinning_needs_a_3d_layout() {
+        assert!(matches!(args(&["--spin"]), Err(ConfigError::InvalidValue { option: "spin", .. })));
+        let config = args(&["--spin", "--vertex-layout", "position3-color"]).unwrap();
+        assert!(config.spin);
+    }
+
+    #[test]
+    fn bad_numbers_are_rejected() {
+        let invalid = |flags: &[&str]| match args(flags) {
+            Err(ConfigError::InvalidValue { option, .. }) => option,
+            other => panic!("{:?} was accepted: {:?}", flags, other),
+        };
+        assert_eq!(invalid(&["--width", "wide"]), "width");
+        assert_eq!(invalid(&["--height", "-1"]), "height");
+        assert_eq!(invalid(&["--width", "0"]), "width");
+        assert_eq!(invalid(&["--height", "100000"]), "height");
+        assert_eq!(invalid(&["--msaa", "3"]), "msaa");
+        assert_eq!(invalid(&["--clear-color", "1,0,0"]), "clear-color");
+        assert_eq!(invalid(&["--clear-color", "2,0,0,1"]), "clear-color");
+        assert_eq!(invalid(&["--texture-lod-bias", "blurry"]), "texture-lod-bias");
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let file = config_file("precedence", "width = 640\nheight = 480\nwindow_title = \"From file\"\n");
+        let path_arg = file.path().to_str().unwrap().to_owned();
+
+        let config = args(&["--width", "1024", "--config", &path_arg, "--title", "From flags"]).unwrap();
+        assert_eq!((config.width, config.height), (1024, 480));
+        assert_eq!(config.window_title, "From flags");
+
+        let config = RendererConfig::from_file(file.path()).unwrap();
+        assert_eq!((config.width, config.height, config.window_title.as_str()), (640, 480, "From file"));
+        drop(file);
+
+        assert!(matches!(args(&["--config", &path_arg]), Err(ConfigError::Io(..))));
+
+        let strict = config_file("strict-fixed", "enable_validation_layers = false\n[validation]\nstrict = true\n");
+        let config = args(&["--config", strict.path().to_str().unwrap(), "--validation"]).unwrap();
+        assert!(config.enable_validation_layers && config.validation.strict);
+
+        let config = args(&["--gpu", "1"]).unwrap();
+        assert_eq!(config.gpu, Some(GpuSelector::Index(1)));
+    }
+
+    #[test]
+    fn invalid_config_files_are_rejected() {
+        let unknown_key = config_file("unknown-key", "widht = 640\n");
+        assert!(matches!(RendererConfig::from_file(unknown_key.path()), Err(ConfigError::Parse(..))));
+
+        let empty_lod_range = config_file("lod-range", "[sampler]\nmin_lod = 4.0\nmax_lod = 2.0\n");
+        assert!(matches!(RendererConfig::from_file(empty_lod_range.path()),
+            Err(ConfigError::InvalidValue { option: "texture-max-lod", .. })));
+
+        let strict = config_file("strict", "enable_validation_layers = false\n[validation]\nstrict = true\n");
+        assert!(matches!(RendererConfig::from_file(strict.path()),
+            Err(ConfigError::InvalidValue { option: "strict-validation", .. })));
+
+        let samples = config_file("msaa", "msaa_samples = 6\n");
+        assert!(matches!(RendererConfig::from_file(samples.path()),
+            Err(ConfigError::InvalidValue { option: "msaa", .. })));
+    }
+}
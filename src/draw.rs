@@ -0,0 +1,62 @@
+use crate::transform::Transforms;
+
+/// A mesh uploaded with `Renderer::add_mesh`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle(pub(crate) usize);
+
+/// A texture uploaded with `Renderer::add_texture`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(pub(crate) usize);
+
+/// One mesh drawn with its own transforms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Draw {
+    pub mesh: MeshHandle,
+    pub transforms: Transforms,
+    /// Sampled by the vertex layouts with texture coordinates, white when `None`
+    pub texture: Option<TextureHandle>,
+}
+
+/// Everything drawn in a frame, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawList {
+    pub draws: Vec<Draw>,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A list drawing only `mesh`.
+    pub fn single(mesh: MeshHandle, transforms: Transforms) -> Self {
+        Self { draws: vec![Draw { mesh, transforms, texture: None }] }
+    }
+
+    pub fn push(&mut self, mesh: MeshHandle, transforms: Transforms) {
+        self.draws.push(Draw { mesh, transforms, texture: None });
+    }
+
+    pub fn push_textured(&mut self, mesh: MeshHandle, texture: TextureHandle, transforms: Transforms) {
+        self.draws.push(Draw { mesh, transforms, texture: Some(texture) });
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// Gives every draw the same transforms.
+    pub fn set_transforms(&mut self, transforms: Transforms) {
+        for draw in &mut self.draws {
+            draw.transforms = transforms;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+}
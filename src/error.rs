@@ -0,0 +1,393 @@
+use std::error;
+use std::fmt;
+
+use vulkano::OomError;
+use vulkano::buffer::cpu_access::ReadLockError;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilderContextError,
+    BeginRenderPassError,
+    BlitImageError,
+    BuildError,
+    CommandBufferExecError,
+    CopyBufferImageError,
+    DrawError,
+    DrawIndexedError,
+};
+use vulkano::descriptor::descriptor_set::{PersistentDescriptorSetBuildError, PersistentDescriptorSetError};
+use vulkano::device::DeviceCreationError;
+use vulkano::format::Format;
+use vulkano::framebuffer::{FramebufferCreationError, RenderPassCreationError};
+use vulkano::image::ImageCreationError;
+use vulkano::instance::{InstanceCreationError, LayersListError, LoadingError};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::GraphicsPipelineCreationError;
+use vulkano::sampler::SamplerCreationError;
+use vulkano::swapchain::{AcquireError, CapabilitiesError, SwapchainCreationError};
+use vulkano::sync::FlushError;
+
+use crate::draw::{MeshHandle, TextureHandle};
+use crate::gpu::GpuSelector;
+use crate::obj::ObjError;
+use crate::scene::SceneError;
+use crate::shader::ShaderError;
+use crate::texture::TextureError;
+use crate::validation::ValidationMessage;
+
+/// Everything that can go wrong while setting up the renderer or drawing a frame.
+#[derive(Debug)]
+pub enum RendererError {
+    Loading(LoadingError),
+    LayersList(LayersListError),
+    InstanceCreation(InstanceCreationError),
+    SurfaceCreation(vulkano_win::CreationError),
+    /// No physical device supports the required queues, extensions and surface formats
+    NoSuitableDevice,
+    /// No physical device matches the `--gpu` selector
+    NoMatchingDevice(GpuSelector),
+    /// The device picked with `--gpu` cannot be used
+    UnsuitableDevice { name: String, problems: Vec<String> },
+    DeviceCreation(DeviceCreationError),
+    SurfaceCapabilities(CapabilitiesError),
+    SwapchainCreation(SwapchainCreationError),
+    RenderPassCreation(RenderPassCreationError),
+    OutOfMemory(OomError),
+    GraphicsPipelineCreation(GraphicsPipelineCreationError),
+    DescriptorSet(PersistentDescriptorSetError),
+    DescriptorSetBuild(PersistentDescriptorSetBuildError),
+    FramebufferCreation(FramebufferCreationError),
+    ImageCreation(ImageCreationError),
+    MemoryAllocation(DeviceMemoryAllocError),
+    CommandBufferContext(AutoCommandBufferBuilderContextError),
+    BeginRenderPass(BeginRenderPassError),
+    Draw(DrawError),
+    DrawIndexed(DrawIndexedError),
+    CopyBufferImage(CopyBufferImageError),
+    BlitImage(BlitImageError),
+    CommandBufferBuild(BuildError),
+    CommandBufferExec(CommandBufferExecError),
+    Acquire(AcquireError),
+    Flush(FlushError),
+    ReadLock(ReadLockError),
+    /// The swapchain was out of date, no frame was rendered
+    FrameSkipped,
+    /// The rendered image's format cannot be converted to RGBA8
+    UnsupportedReadbackFormat(Format),
+    PngEncoding(png::EncodingError),
+    ObjLoading(ObjError),
+    GltfLoading(SceneError),
+    TextureLoading(TextureError),
+    ShaderCompilation(ShaderError),
+    /// The shaders do not fit the vertex layout or each other
+    ShaderInterfaceMismatch { vertex: &'static str, problems: Vec<String> },
+    SamplerCreation(SamplerCreationError),
+    /// A mesh index points past the end of its vertices
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    /// A draw refers to a mesh that was not added to the renderer
+    UnknownMesh(MeshHandle),
+    /// A draw refers to a texture that was not added to the renderer
+    UnknownTexture(TextureHandle),
+    /// Validation errors were reported while strict validation is enabled
+    Validation(Vec<ValidationMessage>),
+    /// Strict validation was requested but no validation layer is installed, it would pass without checking anything
+    ValidationLayerUnavailable,
+}
+
+impl error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RendererError::Loading(e) => Some(e),
+            RendererError::LayersList(e) => Some(e),
+            RendererError::InstanceCreation(e) => Some(e),
+            RendererError::SurfaceCreation(e) => Some(e),
+            RendererError::NoSuitableDevice => None,
+            RendererError::NoMatchingDevice(_) => None,
+            RendererError::UnsuitableDevice { .. } => None,
+            RendererError::DeviceCreation(e) => Some(e),
+            RendererError::SurfaceCapabilities(e) => Some(e),
+            RendererError::SwapchainCreation(e) => Some(e),
+            RendererError::RenderPassCreation(e) => Some(e),
+            RendererError::OutOfMemory(e) => Some(e),
+            RendererError::GraphicsPipelineCreation(e) => Some(e),
+            RendererError::DescriptorSet(e) => Some(e),
+            RendererError::DescriptorSetBuild(e) => Some(e),
+            RendererError::FramebufferCreation(e) => Some(e),
+            RendererError::ImageCreation(e) => Some(e),
+            RendererError::MemoryAllocation(e) => Some(e),
+            RendererError::CommandBufferContext(e) => Some(e),
+            RendererError::BeginRenderPass(e) => Some(e),
+            RendererError::Draw(e) => Some(e),
+            RendererError::DrawIndexed(e) => Some(e),
+            RendererError::CopyBufferImage(e) => Some(e),
+            RendererError::BlitImage(e) => Some(e),
+            RendererError::CommandBufferBuild(e) => Some(e),
+            RendererError::CommandBufferExec(e) => Some(e),
+            RendererError::Acquire(e) => Some(e),
+            RendererError::Flush(e) => Some(e),
+            RendererError::ReadLock(e) => Some(e),
+            RendererError::FrameSkipped => None,
+            RendererError::UnsupportedReadbackFormat(_) => None,
+            RendererError::PngEncoding(e) => Some(e),
+            RendererError::ObjLoading(e) => Some(e),
+            RendererError::GltfLoading(e) => Some(e),
+            RendererError::TextureLoading(e) => Some(e),
+            RendererError::ShaderCompilation(e) => Some(e),
+            RendererError::ShaderInterfaceMismatch { .. } => None,
+            RendererError::SamplerCreation(e) => Some(e),
+            RendererError::IndexOutOfRange { .. } => None,
+            RendererError::UnknownMesh(_) => None,
+            RendererError::UnknownTexture(_) => None,
+            RendererError::Validation(_) => None,
+            RendererError::ValidationLayerUnavailable => None,
+        }
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::Loading(e) => write!(fmt, "failed to load the Vulkan library: {}", e),
+            RendererError::LayersList(e) => write!(fmt, "failed to list instance layers: {}", e),
+            RendererError::InstanceCreation(e) => write!(fmt, "failed to create Vulkan instance: {}", e),
+            RendererError::SurfaceCreation(e) => write!(fmt, "failed to create window surface: {}", e),
+            RendererError::NoSuitableDevice => write!(fmt, "failed to find a suitable GPU"),
+            RendererError::NoMatchingDevice(selector) => write!(fmt, "no GPU matches {}", selector),
+            RendererError::UnsuitableDevice { name, problems } =>
+                write!(fmt, "GPU {} is not suitable: {}", name, problems.join("; ")),
+            RendererError::DeviceCreation(e) => write!(fmt, "failed to create logical device: {}", e),
+            RendererError::SurfaceCapabilities(e) => write!(fmt, "failed to get surface capabilities: {}", e),
+            RendererError::SwapchainCreation(e) => write!(fmt, "failed to create swap chain: {}", e),
+            RendererError::RenderPassCreation(e) => write!(fmt, "failed to create render pass: {}", e),
+            RendererError::OutOfMemory(e) => write!(fmt, "out of memory: {}", e),
+            RendererError::GraphicsPipelineCreation(e) => write!(fmt, "failed to create graphics pipeline: {}", e),
+            RendererError::DescriptorSet(e) => write!(fmt, "failed to add descriptor: {}", e),
+            RendererError::DescriptorSetBuild(e) => write!(fmt, "failed to build descriptor set: {}", e),
+            RendererError::FramebufferCreation(e) => write!(fmt, "failed to create framebuffer: {}", e),
+            RendererError::ImageCreation(e) => write!(fmt, "failed to create image: {}", e),
+            RendererError::MemoryAllocation(e) => write!(fmt, "failed to allocate device memory: {}", e),
+            RendererError::CommandBufferContext(e) => write!(fmt, "failed to record command buffer: {}", e),
+            RendererError::BeginRenderPass(e) => write!(fmt, "failed to begin render pass: {}", e),
+            RendererError::Draw(e) => write!(fmt, "failed to record draw command: {}", e),
+            RendererError::DrawIndexed(e) => write!(fmt, "failed to record indexed draw command: {}", e),
+            RendererError::CopyBufferImage(e) => write!(fmt, "failed to record image copy: {}", e),
+            RendererError::BlitImage(e) => write!(fmt, "failed to record image blit: {}", e),
+            RendererError::CommandBufferBuild(e) => write!(fmt, "failed to build command buffer: {}", e),
+            RendererError::CommandBufferExec(e) => write!(fmt, "failed to execute command buffer: {}", e),
+            RendererError::Acquire(e) => write!(fmt, "failed to acquire next image: {}", e),
+            RendererError::Flush(e) => write!(fmt, "failed to flush frame: {}", e),
+            RendererError::ReadLock(e) => write!(fmt, "failed to map readback buffer: {}", e),
+            RendererError::FrameSkipped => write!(fmt, "the swap chain was out of date, no frame was rendered"),
+            RendererError::UnsupportedReadbackFormat(format) =>
+                write!(fmt, "frame readback does not support format {:?}", format),
+            RendererError::PngEncoding(e) => write!(fmt, "failed to write PNG: {}", e),
+            RendererError::ObjLoading(e) => write!(fmt, "failed to load mesh: {}", e),
+            RendererError::GltfLoading(e) => write!(fmt, "failed to load glTF scene: {}", e),
+            RendererError::TextureLoading(e) => write!(fmt, "failed to load texture: {}", e),
+            RendererError::ShaderCompilation(e) => write!(fmt, "failed to compile shader: {}", e),
+            RendererError::ShaderInterfaceMismatch { vertex, problems } =>
+                write!(fmt, "shaders do not match the `{}` vertex layout: {}", vertex, problems.join("; ")),
+            RendererError::SamplerCreation(e) => write!(fmt, "failed to create sampler: {}", e),
+            RendererError::IndexOutOfRange { index, vertex_count } =>
+                write!(fmt, "mesh index {} is out of range for {} vertices", index, vertex_count),
+            RendererError::UnknownMesh(mesh) => write!(fmt, "no mesh was added with handle {}", mesh.0),
+            RendererError::UnknownTexture(texture) => write!(fmt, "no texture was added with handle {}", texture.0),
+            RendererError::Validation(messages) => {
+                write!(fmt, "{} validation error(s):", messages.len())?;
+                messages.iter().try_for_each(|message| write!(fmt, "\n    {}", message))
+            }
+            RendererError::ValidationLayerUnavailable =>
+                write!(fmt, "strict validation requires a validation layer, but none is installed"),
+        }
+    }
+}
+
+impl From<ObjError> for RendererError {
+    fn from(err: ObjError) -> Self {
+        RendererError::ObjLoading(err)
+    }
+}
+
+impl From<SceneError> for RendererError {
+    fn from(err: SceneError) -> Self {
+        RendererError::GltfLoading(err)
+    }
+}
+
+impl From<TextureError> for RendererError {
+    fn from(err: TextureError) -> Self {
+        RendererError::TextureLoading(err)
+    }
+}
+
+impl From<ShaderError> for RendererError {
+    fn from(err: ShaderError) -> Self {
+        RendererError::ShaderCompilation(err)
+    }
+}
+
+impl From<SamplerCreationError> for RendererError {
+    fn from(err: SamplerCreationError) -> Self {
+        RendererError::SamplerCreation(err)
+    }
+}
+
+impl From<LoadingError> for RendererError {
+    fn from(err: LoadingError) -> Self {
+        RendererError::Loading(err)
+    }
+}
+
+impl From<LayersListError> for RendererError {
+    fn from(err: LayersListError) -> Self {
+        RendererError::LayersList(err)
+    }
+}
+
+impl From<InstanceCreationError> for RendererError {
+    fn from(err: InstanceCreationError) -> Self {
+        RendererError::InstanceCreation(err)
+    }
+}
+
+impl From<vulkano_win::CreationError> for RendererError {
+    fn from(err: vulkano_win::CreationError) -> Self {
+        RendererError::SurfaceCreation(err)
+    }
+}
+
+impl From<DeviceCreationError> for RendererError {
+    fn from(err: DeviceCreationError) -> Self {
+        RendererError::DeviceCreation(err)
+    }
+}
+
+impl From<CapabilitiesError> for RendererError {
+    fn from(err: CapabilitiesError) -> Self {
+        RendererError::SurfaceCapabilities(err)
+    }
+}
+
+impl From<SwapchainCreationError> for RendererError {
+    fn from(err: SwapchainCreationError) -> Self {
+        RendererError::SwapchainCreation(err)
+    }
+}
+
+impl From<RenderPassCreationError> for RendererError {
+    fn from(err: RenderPassCreationError) -> Self {
+        RendererError::RenderPassCreation(err)
+    }
+}
+
+impl From<OomError> for RendererError {
+    fn from(err: OomError) -> Self {
+        RendererError::OutOfMemory(err)
+    }
+}
+
+impl From<GraphicsPipelineCreationError> for RendererError {
+    fn from(err: GraphicsPipelineCreationError) -> Self {
+        RendererError::GraphicsPipelineCreation(err)
+    }
+}
+
+impl From<PersistentDescriptorSetError> for RendererError {
+    fn from(err: PersistentDescriptorSetError) -> Self {
+        RendererError::DescriptorSet(err)
+    }
+}
+
+impl From<PersistentDescriptorSetBuildError> for RendererError {
+    fn from(err: PersistentDescriptorSetBuildError) -> Self {
+        RendererError::DescriptorSetBuild(err)
+    }
+}
+
+impl From<FramebufferCreationError> for RendererError {
+    fn from(err: FramebufferCreationError) -> Self {
+        RendererError::FramebufferCreation(err)
+    }
+}
+
+impl From<ImageCreationError> for RendererError {
+    fn from(err: ImageCreationError) -> Self {
+        RendererError::ImageCreation(err)
+    }
+}
+
+impl From<DeviceMemoryAllocError> for RendererError {
+    fn from(err: DeviceMemoryAllocError) -> Self {
+        RendererError::MemoryAllocation(err)
+    }
+}
+
+impl From<AutoCommandBufferBuilderContextError> for RendererError {
+    fn from(err: AutoCommandBufferBuilderContextError) -> Self {
+        RendererError::CommandBufferContext(err)
+    }
+}
+
+impl From<BeginRenderPassError> for RendererError {
+    fn from(err: BeginRenderPassError) -> Self {
+        RendererError::BeginRenderPass(err)
+    }
+}
+
+impl From<DrawError> for RendererError {
+    fn from(err: DrawError) -> Self {
+        RendererError::Draw(err)
+    }
+}
+
+impl From<DrawIndexedError> for RendererError {
+    fn from(err: DrawIndexedError) -> Self {
+        RendererError::DrawIndexed(err)
+    }
+}
+
+impl From<CopyBufferImageError> for RendererError {
+    fn from(err: CopyBufferImageError) -> Self {
+        RendererError::CopyBufferImage(err)
+    }
+}
+
+impl From<BlitImageError> for RendererError {
+    fn from(err: BlitImageError) -> Self {
+        RendererError::BlitImage(err)
+    }
+}
+
+impl From<BuildError> for RendererError {
+    fn from(err: BuildError) -> Self {
+        RendererError::CommandBufferBuild(err)
+    }
+}
+
+impl From<CommandBufferExecError> for RendererError {
+    fn from(err: CommandBufferExecError) -> Self {
+        RendererError::CommandBufferExec(err)
+    }
+}
+
+impl From<AcquireError> for RendererError {
+    fn from(err: AcquireError) -> Self {
+        RendererError::Acquire(err)
+    }
+}
+
+impl From<FlushError> for RendererError {
+    fn from(err: FlushError) -> Self {
+        RendererError::Flush(err)
+    }
+}
+
+impl From<ReadLockError> for RendererError {
+    fn from(err: ReadLockError) -> Self {
+        RendererError::ReadLock(err)
+    }
+}
+
+impl From<png::EncodingError> for RendererError {
+    fn from(err: png::EncodingError) -> Self {
+        RendererError::PngEncoding(err)
+    }
+}
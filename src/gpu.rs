@@ -0,0 +1,332 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize, Serializer};
+use vulkano::instance::{PhysicalDevice, PhysicalDeviceType, Version};
+use vulkano::swapchain::Surface;
+use winit::window::Window;
+
+/// Forces the renderer onto one physical device, by enumeration index or by a
+/// case-insensitive substring of its name.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum GpuSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl GpuSelector {
+    pub fn matches(&self, device: &PhysicalDevice) -> bool {
+        match self {
+            GpuSelector::Index(index) => device.index() == *index,
+            GpuSelector::Name(name) => device.name().to_lowercase().contains(&name.to_lowercase()),
+        }
+    }
+}
+
+impl FromStr for GpuSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => GpuSelector::Index(index),
+            Err(_) => GpuSelector::Name(s.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for GpuSelector {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpuSelector::Index(index) => write!(fmt, "index {}", index),
+            GpuSelector::Name(name) => write!(fmt, "name `{}`", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueFamilyInfo {
+    pub id: u32,
+    pub queue_count: usize,
+    pub graphics: bool,
+    pub compute: bool,
+    pub transfer: bool,
+    /// `None` when there is no surface to check presentation against
+    pub present: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryHeapInfo {
+    pub size: usize,
+    pub device_local: bool,
+}
+
+/// Points given for the device type, integrated GPUs share memory with the CPU and are usually slower
+const DISCRETE_GPU_SCORE: i64 = 1000;
+const INTEGRATED_GPU_SCORE: i64 = 500;
+const VIRTUAL_GPU_SCORE: i64 = 250;
+const CPU_SCORE: i64 = 100;
+
+/// One point per 256 MiB of device-local memory
+const MEMORY_SCORE_UNIT: usize = 256 * 1024 * 1024;
+/// One point per 1024 pixels of maximum 2D image size
+const IMAGE_DIMENSION_SCORE_UNIT: u32 = 1024;
+/// Subtracted when graphics and presentation need different queue families
+const SEPARATE_PRESENT_QUEUE_PENALTY: i64 = 50;
+
+/// How a suitable device's score was computed. Devices are ranked by type first, so that no amount of memory makes
+/// a CPU implementation win over a GPU, and by total within a type.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceScore {
+    pub device_type: i64,
+    pub memory: i64,
+    pub limits: i64,
+    /// Zero or negative
+    pub present_queue: i64,
+}
+
+impl DeviceScore {
+    pub fn total(&self) -> i64 {
+        self.device_type + self.memory + self.limits + self.present_queue
+    }
+
+    /// Sort key of the ranking, higher is better.
+    pub fn rank(&self) -> (i64, i64) {
+        (self.device_type, self.total())
+    }
+}
+
+impl fmt::Display for DeviceScore {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} (type {}, memory {}, limits {}, present queue {})",
+            self.total(), self.device_type, self.memory, self.limits, self.present_queue)
+    }
+}
+
+/// Description of a physical device as printed by `--list-gpus`.
+#[derive(Clone, Debug, Serialize)]
+pub struct GpuInfo {
+    pub index: usize,
+    pub name: String,
+    #[serde(serialize_with = "serialize_debug")]
+    pub device_type: PhysicalDeviceType,
+    #[serde(serialize_with = "serialize_display")]
+    pub api_version: Version,
+    pub driver_version: u32,
+    pub queue_families: Vec<QueueFamilyInfo>,
+    pub memory_heaps: Vec<MemoryHeapInfo>,
+    pub max_image_dimension_2d: u32,
+    /// Why the renderer cannot use this device, empty if it is suitable
+    pub problems: Vec<String>,
+}
+
+impl GpuInfo {
+    pub fn new(device: &PhysicalDevice, surface: Option<&Arc<Surface<Window>>>, problems: Vec<String>) -> Self {
+        let queue_families = device.queue_families()
+            .map(|family| QueueFamilyInfo {
+                id: family.id(),
+                queue_count: family.queues_count(),
+                graphics: family.supports_graphics(),
+                compute: family.supports_compute(),
+                transfer: family.explicitly_supports_transfers(),
+                present: surface.map(|surface| surface.is_supported(family).unwrap_or(false)),
+            })
+            .collect();
+        let memory_heaps = device.memory_heaps()
+            .map(|heap| MemoryHeapInfo { size: heap.size(), device_local: heap.is_device_local() })
+            .collect();
+
+        Self {
+            index: device.index(),
+            name: device.name(),
+            device_type: device.ty(),
+            api_version: device.api_version(),
+            driver_version: device.driver_version(),
+            queue_families,
+            memory_heaps,
+            max_image_dimension_2d: device.limits().max_image_dimension_2d(),
+            problems,
+        }
+    }
+
+    pub fn is_suitable(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Whether presentation was checked and no queue family supports both graphics and presenting.
+    pub fn needs_separate_present_queue(&self) -> bool {
+        let present_checked = self.queue_families.iter().any(|family| family.present.is_some());
+        present_checked && !self.queue_families.iter()
+            .any(|family| family.graphics && family.present == Some(true))
+    }
+
+    pub fn device_local_memory(&self) -> usize {
+        self.memory_heaps.iter()
+            .filter(|heap| heap.device_local)
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    /// `None` if the device is not suitable.
+    pub fn score(&self) -> Option<DeviceScore> {
+        if !self.is_suitable() {
+            return None;
+        }
+
+        let device_type = match self.device_type {
+            PhysicalDeviceType::DiscreteGpu => DISCRETE_GPU_SCORE,
+            PhysicalDeviceType::IntegratedGpu => INTEGRATED_GPU_SCORE,
+            PhysicalDeviceType::VirtualGpu => VIRTUAL_GPU_SCORE,
+            PhysicalDeviceType::Cpu => CPU_SCORE,
+            PhysicalDeviceType::Other => 0,
+        };
+        let present_queue = if self.needs_separate_present_queue() { -SEPARATE_PRESENT_QUEUE_PENALTY } else { 0 };
+
+        Some(DeviceScore {
+            device_type,
+            memory: (self.device_local_memory() / MEMORY_SCORE_UNIT) as i64,
+            limits: (self.max_image_dimension_2d / IMAGE_DIMENSION_SCORE_UNIT) as i64,
+            present_queue,
+        })
+    }
+}
+
+/// Highest sample count in the `supported` bitmask (as in `VkSampleCountFlags`) that does not exceed `requested`.
+pub fn clamp_sample_count(requested: u32, supported: u32) -> u32 {
+    (0..32).rev()
+        .map(|bit| 1 << bit)
+        .find(|&samples| samples <= requested && supported & samples != 0)
+        .unwrap_or(1)
+}
+
+fn serialize_debug<T: fmt::Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", value))
+}
+
+fn serialize_display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+impl fmt::Display for GpuInfo {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "[{}] {} ({:?})", self.index, self.name, self.device_type)?;
+        writeln!(fmt, "    API version: {}, driver version: {:#x}", self.api_version, self.driver_version)?;
+
+        writeln!(fmt, "    Queue families:")?;
+        for family in &self.queue_families {
+            let mut capabilities = vec![];
+            if family.graphics { capabilities.push("graphics"); }
+            if family.compute { capabilities.push("compute"); }
+            if family.transfer { capabilities.push("transfer"); }
+            if family.present == Some(true) { capabilities.push("present"); }
+            writeln!(fmt, "        #{}: {} queue(s), {}", family.id, family.queue_count, capabilities.join(", "))?;
+        }
+
+        writeln!(fmt, "    Memory heaps:")?;
+        for heap in &self.memory_heaps {
+            writeln!(fmt, "        {} MiB{}", heap.size / (1024 * 1024),
+                if heap.device_local { ", device local" } else { "" })?;
+        }
+
+        match self.score() {
+            Some(score) => write!(fmt, "    Suitable: yes, score {}", score),
+            None => write!(fmt, "    Suitable: no ({})", self.problems.join("; ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: usize = 1024 * 1024 * 1024;
+
+    fn fake_device(device_type: PhysicalDeviceType, device_local_memory: usize, present_with_graphics: bool) -> GpuInfo {
+        GpuInfo {
+            index: 0,
+            name: "Fake GPU".to_owned(),
+            device_type,
+            api_version: Version { major: 1, minor: 2, patch: 0 },
+            driver_version: 0,
+            queue_families: vec![
+                QueueFamilyInfo { id: 0, queue_count: 1, graphics: true, compute: true, transfer: true,
+                    present: Some(present_with_graphics) },
+                QueueFamilyInfo { id: 1, queue_count: 1, graphics: false, compute: false, transfer: true,
+                    present: Some(true) },
+            ],
+            memory_heaps: vec![
+                MemoryHeapInfo { size: device_local_memory, device_local: true },
+                MemoryHeapInfo { size: 16 * GIB, device_local: false },
+            ],
+            max_image_dimension_2d: 16384,
+            problems: vec![],
+        }
+    }
+
+    #[test]
+    fn score_breakdown() {
+        let score = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, true).score().unwrap();
+        assert_eq!(score, DeviceScore { device_type: 1000, memory: 32, limits: 16, present_queue: 0 });
+        assert_eq!(score.total(), 1048);
+    }
+
+    #[test]
+    fn device_types_are_ranked() {
+        let rank = |device_type| fake_device(device_type, 2 * GIB, true).score().unwrap().rank();
+        assert!(rank(PhysicalDeviceType::DiscreteGpu) > rank(PhysicalDeviceType::IntegratedGpu));
+        assert!(rank(PhysicalDeviceType::IntegratedGpu) > rank(PhysicalDeviceType::Cpu));
+    }
+
+    #[test]
+    fn device_type_outweighs_memory() {
+        //lavapipe reports the host's RAM as device-local memory
+        let cpu = fake_device(PhysicalDeviceType::Cpu, 512 * GIB, true).score().unwrap();
+        let discrete = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, false).score().unwrap();
+        assert!(cpu.total() > discrete.total());
+        assert!(discrete.rank() > cpu.rank());
+    }
+
+    #[test]
+    fn more_device_local_memory_wins() {
+        let small = fake_device(PhysicalDeviceType::DiscreteGpu, 2 * GIB, true).score().unwrap();
+        let large = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, true).score().unwrap();
+        assert!(large.rank() > small.rank());
+    }
+
+    #[test]
+    fn separate_present_queue_is_penalized() {
+        let device = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, false);
+        assert!(device.needs_separate_present_queue());
+        assert_eq!(device.score().unwrap().present_queue, -SEPARATE_PRESENT_QUEUE_PENALTY);
+    }
+
+    #[test]
+    fn headless_devices_are_not_penalized() {
+        let mut device = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, false);
+        device.queue_families.iter_mut().for_each(|family| family.present = None);
+        assert!(!device.needs_separate_present_queue());
+    }
+
+    #[test]
+    fn unsuitable_devices_have_no_score() {
+        let mut device = fake_device(PhysicalDeviceType::DiscreteGpu, 8 * GIB, true);
+        device.problems.push("missing required features".to_owned());
+        assert_eq!(device.score(), None);
+    }
+
+    #[test]
+    fn selector_parses_index_or_name() {
+        assert_eq!("1".parse::<GpuSelector>().unwrap(), GpuSelector::Index(1));
+        assert_eq!("nvidia".parse::<GpuSelector>().unwrap(), GpuSelector::Name("nvidia".to_owned()));
+    }
+
+    #[test]
+    fn sample_count_is_clamped_to_supported() {
+        let supported = 1 | 2 | 4 | 8;
+        assert_eq!(clamp_sample_count(4, supported), 4);
+        assert_eq!(clamp_sample_count(16, supported), 8);
+        assert_eq!(clamp_sample_count(8, 1 | 4), 4);
+        assert_eq!(clamp_sample_count(1, supported), 1);
+    }
+}
@@ -0,0 +1,319 @@
+use vulkano::descriptor::descriptor::{DescriptorBufferDesc, DescriptorDescTy};
+use vulkano::format::{Format, FormatTy};
+use vulkano::pipeline::shader::{ShaderInterfaceDef, ShaderInterfaceDefEntry};
+use vulkano::pipeline::vertex::{Vertex, VertexMemberTy};
+
+use crate::error::RendererError;
+use crate::reflect::DescriptorBinding;
+
+/// Set 0 binding of the transforms every draw binds
+const TRANSFORMS_BINDING: u32 = 0;
+/// Set 0 binding of the texture textured layouts bind
+const TEXTURE_BINDING: u32 = 1;
+
+/// Vertex shader inputs that the fields of `V` cannot feed, one readable line each.
+///
+/// Inputs are matched to the fields listed in `impl_vertex!` by name, like vulkano does, and the field has to hold
+/// the same kind and amount of data as the input's type.
+pub fn vertex_input_problems<V, I>(input: &I) -> Vec<String>
+    where V: Vertex,
+          I: ShaderInterfaceDef
+{
+    let vertex = type_name::<V>();
+    let mut problems = vec![];
+    for element in input.elements() {
+        let location_count = element.location.end - element.location.start;
+        let name = match &element.name {
+            Some(name) => name,
+            None => {
+                problems.push(format!("vertex input at location {} has no name to match a field of `{}` with",
+                    element.location.start, vertex));
+                continue;
+            }
+        };
+        let member = match V::member(name) {
+            Some(member) => member,
+            None => {
+                problems.push(format!("vertex input `{}` ({}, location {}) has no matching field in `{}`",
+                    name, glsl_type(element.format, location_count), element.location.start, vertex));
+                continue;
+            }
+        };
+
+        let same_kind = matches!((member.ty, element.format.ty()),
+            (VertexMemberTy::F32, FormatTy::Float) | (VertexMemberTy::F64, FormatTy::Float)
+                | (VertexMemberTy::I8, FormatTy::Sint) | (VertexMemberTy::I16, FormatTy::Sint)
+                | (VertexMemberTy::I32, FormatTy::Sint) | (VertexMemberTy::U8, FormatTy::Uint)
+                | (VertexMemberTy::U16, FormatTy::Uint) | (VertexMemberTy::U32, FormatTy::Uint));
+        if !same_kind || !member.ty.matches(member.array_size, element.format, location_count) {
+            problems.push(format!("vertex input `{}` (location {}) is {} but `{}::{}` is {}",
+                name, element.location.start, glsl_type(element.format, location_count), vertex, name,
+                rust_type(member.ty, member.array_size)));
+        }
+    }
+    problems
+}
+
+/// Differences between the vertex shader's outputs and the fragment shader's inputs, one readable line each.
+///
+/// vulkano wants both sides to declare the same locations with the same formats, so unread outputs count too.
+pub fn stage_interface_problems<O, I>(outputs: &O, inputs: &I) -> Vec<String>
+    where O: ShaderInterfaceDef,
+          I: ShaderInterfaceDef
+{
+    let covering = |elements: Vec<ShaderInterfaceDefEntry>, location: u32| elements.into_iter()
+        .find(|element| element.location.start <= location && location < element.location.end);
+
+    let mut problems = vec![];
+    for output in outputs.elements() {
+        let input = match covering(inputs.elements().collect(), output.location.start) {
+            Some(input) => input,
+            None => {
+                problems.push(format!("vertex output {} is not read by the fragment shader", describe(&output)));
+                continue;
+            }
+        };
+        if input.location != output.location || input.format != output.format {
+            problems.push(format!("vertex output {} is {} but fragment input {} is {}",
+                describe(&output), glsl_type(output.format, output.location.end - output.location.start),
+                describe(&input), glsl_type(input.format, input.location.end - input.location.start)));
+        }
+    }
+    for input in inputs.elements() {
+        if covering(outputs.elements().collect(), input.location.start).is_none() {
+            problems.push(format!("fragment input {} is not written by the vertex shader", describe(&input)));
+        }
+    }
+    problems
+}
+
+/// Descriptors of the shaders' stages that differ from what the renderer binds, one readable line each.
+///
+/// Every draw binds its transforms as a uniform buffer at set 0, binding 0, and the textured layouts a combined image
+/// sampler at binding 1. Bindings declared by several stages are checked once.
+pub fn descriptor_problems(descriptors: &[DescriptorBinding], textured: bool) -> Vec<String> {
+    let mut bound = vec![(TRANSFORMS_BINDING, "uniform buffer")];
+    if textured {
+        bound.push((TEXTURE_BINDING, "combined image sampler"));
+    }
+
+    let mut problems = vec![];
+    let mut declared = vec![];
+    for descriptor in descriptors {
+        if declared.contains(&(descriptor.set, descriptor.binding)) {
+            continue;
+        }
+        declared.push((descriptor.set, descriptor.binding));
+
+        let name = match &descriptor.name {
+            Some(name) => format!("`{}` (set {}, binding {})", name, descriptor.set, descriptor.binding),
+            None => format!("at set {}, binding {}", descriptor.set, descriptor.binding),
+        };
+        let kind = descriptor_kind(&descriptor.desc.ty);
+        let expected = bound.iter().find(|&&(binding, _)| descriptor.set == 0 && descriptor.binding == binding);
+        match expected {
+            None => problems.push(format!("descriptor {} is a {} the renderer does not bind", name, kind)),
+            Some(&(_, expected)) if expected != kind =>
+                problems.push(format!("descriptor {} is a {} but the renderer binds a {}", name, kind, expected)),
+            Some(_) if descriptor.desc.array_count != 1 =>
+                problems.push(format!("descriptor {} is an array of {} but the renderer binds one", name,
+                    descriptor.desc.array_count)),
+            Some(_) => {}
+        }
+    }
+    for &(binding, kind) in &bound {
+        if !declared.contains(&(0, binding)) {
+            problems.push(format!("no descriptor at set 0, binding {} for the {} the renderer binds", binding, kind));
+        }
+    }
+    problems
+}
+
+/// Fails with every descriptor problem of a vertex and fragment shader loaded at runtime listed, before the
+/// descriptor sets are built at draw time.
+pub(crate) fn check_descriptors<V: Vertex>(
+    vertex_descriptors: &[DescriptorBinding],
+    fragment_descriptors: &[DescriptorBinding],
+    textured: bool,
+) -> Result<(), RendererError> {
+    let descriptors = [vertex_descriptors, fragment_descriptors].concat();
+    let problems = descriptor_problems(&descriptors, textured);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(RendererError::ShaderInterfaceMismatch { vertex: type_name::<V>(), problems })
+    }
+}
+
+/// Runs both checks, so that mismatched shaders fail with every problem listed rather than vulkano's first one.
+pub(crate) fn check_pipeline_interfaces<V, VI, VO, FI>(
+    vertex_input: &VI,
+    vertex_output: &VO,
+    fragment_input: &FI,
+) -> Result<(), RendererError>
+    where V: Vertex,
+          VI: ShaderInterfaceDef,
+          VO: ShaderInterfaceDef,
+          FI: ShaderInterfaceDef
+{
+    let mut problems = vertex_input_problems::<V, _>(vertex_input);
+    problems.extend(stage_interface_problems(vertex_output, fragment_input));
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(RendererError::ShaderInterfaceMismatch { vertex: type_name::<V>(), problems })
+    }
+}
+
+fn type_name<V>() -> &'static str {
+    let path = std::any::type_name::<V>();
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+fn descriptor_kind(ty: &DescriptorDescTy) -> &'static str {
+    match ty {
+        DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: false, .. }) => "uniform buffer",
+        DescriptorDescTy::Buffer(_) => "storage buffer",
+        DescriptorDescTy::CombinedImageSampler(_) => "combined image sampler",
+        DescriptorDescTy::Image(_) => "image",
+        DescriptorDescTy::Sampler => "sampler",
+        DescriptorDescTy::TexelBuffer { .. } => "texel buffer",
+        DescriptorDescTy::InputAttachment { .. } => "input attachment",
+    }
+}
+
+fn describe(element: &ShaderInterfaceDefEntry) -> String {
+    match &element.name {
+        Some(name) => format!("`{}` (location {})", name, element.location.start),
+        None => format!("at location {}", element.location.start),
+    }
+}
+
+/// How the format was most likely declared in GLSL, with a length for matrices and arrays.
+fn glsl_type(format: Format, location_count: u32) -> String {
+    use vulkano::format::Format::*;
+
+    let ty = match format {
+        R32Sfloat => "float",
+        R32G32Sfloat => "vec2",
+        R32G32B32Sfloat => "vec3",
+        R32G32B32A32Sfloat => "vec4",
+        R64Sfloat => "double",
+        R64G64Sfloat => "dvec2",
+        R64G64B64Sfloat => "dvec3",
+        R64G64B64A64Sfloat => "dvec4",
+        R32Sint => "int",
+        R32G32Sint => "ivec2",
+        R32G32B32Sint => "ivec3",
+        R32G32B32A32Sint => "ivec4",
+        R32Uint => "uint",
+        R32G32Uint => "uvec2",
+        R32G32B32Uint => "uvec3",
+        R32G32B32A32Uint => "uvec4",
+        _ => return format!("{:?}", format),
+    };
+    if location_count > 1 {
+        format!("{}[{}]", ty, location_count)
+    } else {
+        ty.to_owned()
+    }
+}
+
+fn rust_type(ty: VertexMemberTy, array_size: usize) -> String {
+    let ty = format!("{:?}", ty).to_lowercase();
+    if array_size > 1 {
+        format!("[{}; {}]", ty, array_size)
+    } else {
+        ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vulkano::descriptor::descriptor::{DescriptorDesc, ShaderStages};
+
+    use crate::reflect::{InterfaceVariable, ReflectedInterface};
+    use crate::vertex::Vertex as TriangleVertex;
+
+    fn interface(variables: &[(Option<&str>, u32, Format)]) -> ReflectedInterface {
+        let variables: Vec<_> = variables.iter()
+            .map(|&(name, location, format)| InterfaceVariable {
+                name: name.map(str::to_owned),
+                location,
+                location_count: 1,
+                format,
+            })
+            .collect();
+        ReflectedInterface::new(&variables)
+    }
+
+    #[test]
+    fn vertex_inputs_need_a_matching_field() {
+        let matching = interface(&[
+            (Some("pos"), 0, Format::R32G32Sfloat),
+            (Some("color"), 1, Format::R32G32B32Sfloat),
+        ]);
+        assert!(vertex_input_problems::<TriangleVertex, _>(&matching).is_empty());
+
+        let mismatched = interface(&[
+            (Some("position"), 0, Format::R32G32B32Sfloat),
+            (Some("color"), 1, Format::R32G32B32A32Sfloat),
+            (Some("pos"), 2, Format::R32G32Sint),
+            (None, 3, Format::R32Sfloat),
+        ]);
+        assert_eq!(vertex_input_problems::<TriangleVertex, _>(&mismatched), vec![
+            "vertex input `position` (vec3, location 0) has no matching field in `Vertex`",
+            "vertex input `color` (location 1) is vec4 but `Vertex::color` is [f32; 3]",
+            "vertex input `pos` (location 2) is ivec2 but `Vertex::pos` is [f32; 2]",
+            "vertex input at location 3 has no name to match a field of `Vertex` with",
+        ]);
+    }
+
+    fn descriptor(name: &str, set: u32, binding: u32, ty: DescriptorDescTy) -> DescriptorBinding {
+        let desc = DescriptorDesc { ty, array_count: 1, stages: ShaderStages::none(), readonly: true };
+        DescriptorBinding { set, binding, name: Some(name.to_owned()), desc }
+    }
+
+    #[test]
+    fn descriptors_must_match_the_bound_ones() {
+        let uniform = DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: false });
+        let storage = DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: true });
+        let matching = [descriptor("ubo", 0, 0, uniform.clone()), descriptor("ubo", 0, 0, uniform.clone())];
+        assert!(descriptor_problems(&matching, false).is_empty());
+
+        let mismatched = [
+            descriptor("ubo", 0, 0, storage),
+            descriptor("lights", 1, 0, uniform),
+            descriptor("shadowSampler", 0, 2, DescriptorDescTy::Sampler),
+        ];
+        assert_eq!(descriptor_problems(&mismatched, true), vec![
+            "descriptor `ubo` (set 0, binding 0) is a storage buffer but the renderer binds a uniform buffer",
+            "descriptor `lights` (set 1, binding 0) is a uniform buffer the renderer does not bind",
+            "descriptor `shadowSampler` (set 0, binding 2) is a sampler the renderer does not bind",
+            "no descriptor at set 0, binding 1 for the combined image sampler the renderer binds",
+        ]);
+        assert_eq!(descriptor_problems(&[], false), vec![
+            "no descriptor at set 0, binding 0 for the uniform buffer the renderer binds",
+        ]);
+    }
+
+    #[test]
+    fn stage_outputs_must_match_inputs() {
+        let outputs = interface(&[
+            (Some("fragColor"), 0, Format::R32G32B32Sfloat),
+            (Some("fragUv"), 1, Format::R32G32Sfloat),
+        ]);
+        assert!(stage_interface_problems(&outputs, &outputs).is_empty());
+
+        let inputs = interface(&[
+            (Some("fragColor"), 0, Format::R32G32B32A32Sfloat),
+            (Some("fragNormal"), 2, Format::R32G32B32Sfloat),
+        ]);
+        assert_eq!(stage_interface_problems(&outputs, &inputs), vec![
+            "vertex output `fragColor` (location 0) is vec3 but fragment input `fragColor` (location 0) is vec4",
+            "vertex output `fragUv` (location 1) is not read by the fragment shader",
+            "fragment input `fragNormal` (location 2) is not written by the vertex shader",
+        ]);
+    }
+}
@@ -0,0 +1,45 @@
+//! Vulkan setup shared by the tutorial application and our tools: instance, device and
+//! swapchain management, offscreen rendering and frame readback.
+
+mod capture;
+mod config;
+mod draw;
+mod error;
+mod gpu;
+mod interface;
+mod mesh;
+mod obj;
+mod reflect;
+mod renderer;
+mod report;
+mod scene;
+mod shader;
+#[cfg(test)]
+mod temp_file;
+mod texture;
+mod transform;
+mod validation;
+mod vertex;
+
+pub use capture::FrameCapture;
+pub use config::{ConfigError, PresentModePreference, RendererConfig, USAGE};
+pub use draw::{Draw, DrawList, MeshHandle, TextureHandle};
+pub use error::RendererError;
+pub use gpu::{DeviceScore, GpuInfo, GpuSelector, MemoryHeapInfo, QueueFamilyInfo};
+pub use interface::{descriptor_problems, stage_interface_problems, vertex_input_problems};
+pub use mesh::{Indices, Mesh};
+pub use obj::{Materials, ObjError, ObjGroup, ObjModel, parse_mtl};
+pub use reflect::{DescriptorBinding, InterfaceVariable, ReflectError, ShaderReflection};
+pub use renderer::{QueueFamilyIndices, Renderer};
+pub use report::{CapabilityReport, DeviceReport, LayerReport, SurfaceFormatReport, SurfaceReport};
+pub use scene::{Material, Matrix4, Node, Primitive, Scene, SceneError, SceneMesh};
+pub use shader::{ShaderError, ShaderStage, ShaderWatcher, compile_glsl, load_shader, load_spirv, shader_path};
+pub use texture::{
+    SamplerSettings, Texture, TextureAddressMode, TextureColorSpace, TextureError, TextureFilter,
+};
+pub use transform::{Transforms, perspective};
+pub use vertex::{
+    ColorVertex, LayoutVertex, MeshVertex, NormalColorVertex, NormalUvTangentVertex, NormalUvVertex, Vertex, VertexLayout,
+    vertices,
+};
+pub use validation::{ValidationLog, ValidationMessage, ValidationSettings, ValidationSeverity};
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use vulkano::buffer::ImmutableBuffer;
+
+use vulkano::buffer::BufferAccess;
+
+use crate::vertex::{Vertex, VertexLayout, vertices};
+
+/// Triangle list indices, stored as `u16` whenever every index fits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Picks the smallest index type able to address `vertex_count` vertices. Indices past the end of the vertices
+    /// are kept as they are, never truncated into range, so that `Mesh::out_of_range_index` still finds them.
+    pub fn compact(indices: Vec<u32>, vertex_count: usize) -> Self {
+        let fits_u16 = |index: u32| index <= u32::from(u16::MAX);
+        if vertex_count <= usize::from(u16::MAX) + 1 && indices.iter().all(|&index| fits_u16(index)) {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().max().map(|&index| u32::from(index)),
+            Indices::U32(indices) => indices.iter().max().cloned(),
+        }
+    }
+}
+
+/// Vertices drawn as a triangle list, through `indices` when present.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh<V = Vertex> {
+    pub vertices: Vec<V>,
+    pub indices: Option<Indices>,
+}
+
+impl<V> Mesh<V> {
+    pub fn new(vertices: Vec<V>) -> Self {
+        Self { vertices, indices: None }
+    }
+
+    pub fn indexed(vertices: Vec<V>, indices: Vec<u32>) -> Self {
+        let indices = Indices::compact(indices, vertices.len());
+        Self { vertices, indices: Some(indices) }
+    }
+
+    /// The largest index, if it points past the end of `vertices`.
+    pub fn out_of_range_index(&self) -> Option<u32> {
+        self.indices.as_ref()
+            .and_then(Indices::max)
+            .filter(|&index| index as usize >= self.vertices.len())
+    }
+
+    /// The same mesh with every vertex converted to another layout.
+    pub fn convert<W: From<V>>(&self) -> Mesh<W> where V: Copy {
+        Mesh {
+            vertices: self.vertices.iter().map(|&vertex| W::from(vertex)).collect(),
+            indices: self.indices.clone(),
+        }
+    }
+}
+
+impl Mesh {
+    /// The tutorial's hardcoded triangle.
+    pub fn triangle() -> Self {
+        Self::new(vertices().to_vec())
+    }
+
+    /// Two triangles sharing the diagonal of a square.
+    pub fn quad() -> Self {
+        Self::indexed(
+            vec![
+                Vertex::new([-0.5, -0.5], [1.0, 0.0, 0.0]),
+                Vertex::new([0.5, -0.5], [0.0, 1.0, 0.0]),
+                Vertex::new([0.5, 0.5], [0.0, 0.0, 1.0]),
+                Vertex::new([-0.5, 0.5], [1.0, 1.0, 1.0]),
+            ],
+            vec![0, 1, 2, 2, 3, 0],
+        )
+    }
+}
+
+/// Index buffer uploaded from `Indices`.
+#[derive(Clone)]
+pub(crate) enum IndexBuffer {
+    U16(Arc<ImmutableBuffer<[u16]>>),
+    U32(Arc<ImmutableBuffer<[u32]>>),
+}
+
+/// Buffers of a mesh added to the renderer, drawn with the pipeline built for `layout`.
+#[derive(Clone)]
+pub(crate) struct GpuMesh {
+    pub layout: VertexLayout,
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub index_buffer: Option<IndexBuffer>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_meshes_use_u16_indices() {
+        assert_eq!(Indices::compact(vec![0, 1, 2], 3), Indices::U16(vec![0, 1, 2]));
+        assert_eq!(Indices::compact(vec![65535], 65536), Indices::U16(vec![65535]));
+        assert_eq!(Indices::compact(vec![65536], 65537), Indices::U32(vec![65536]));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_found() {
+        assert_eq!(Mesh::quad().out_of_range_index(), None);
+        assert_eq!(Mesh::triangle().out_of_range_index(), None);
+
+        let mesh = Mesh::indexed(vertices().to_vec(), vec![0, 1, 3]);
+        assert_eq!(mesh.out_of_range_index(), Some(3));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_not_truncated() {
+        let mesh = Mesh::indexed(vertices().to_vec(), vec![0, 1, 65536]);
+        assert_eq!(mesh.indices, Some(Indices::U32(vec![0, 1, 65536])));
+        assert_eq!(mesh.out_of_range_index(), Some(65536));
+    }
+}
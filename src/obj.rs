@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::mesh::Mesh;
+use crate::vertex::MeshVertex;
+
+/// Color of vertices whose faces have no material
+const DEFAULT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// Diffuse colors by material name, read from `.mtl` files.
+pub type Materials = HashMap<String, [f32; 3]>;
+
+/// Consecutive faces sharing a `g`/`o` name and a material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    /// Triangle list indices into `ObjModel::vertices`
+    pub indices: Vec<u32>,
+}
+
+/// Triangulated contents of an OBJ file, vertices are shared between all groups.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjModel {
+    /// Normals and texture coordinates are zero when the face has no `vn`/`vt` reference, OBJ has no tangents and
+    /// colors are the diffuse color of the face's material
+    pub vertices: Vec<MeshVertex>,
+    pub groups: Vec<ObjGroup>,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    /// Syntax or reference error at a 1-based line of `file`
+    Parse { file: String, line: usize, message: String },
+}
+
+impl error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ObjError::Io(_, e) => Some(e),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, e) => write!(fmt, "failed to read {}: {}", path.display(), e),
+            ObjError::Parse { file, line, message } => write!(fmt, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl ObjModel {
+    /// Reads an OBJ file along with the `.mtl` files it references, relative to its directory. Material libraries
+    /// that cannot be read are skipped with a warning, their materials get the default color.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let source = read(path)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut materials = Materials::new();
+        for line in source.lines() {
+            let mut words = line.split_whitespace();
+            if words.next() == Some("mtllib") {
+                for library in words {
+                    let library = directory.join(library);
+                    match read(&library) {
+                        Ok(source) => materials.extend(parse_mtl(&library.display().to_string(), &source)?),
+                        Err(e) => log::warn!("{}, using the default color for its materials", e),
+                    }
+                }
+            }
+        }
+
+        Self::parse(&path.display().to_string(), &source, &materials)
+    }
+
+    /// Parses OBJ source, `file` is only used in error messages.
+    pub fn parse(file: &str, source: &str, materials: &Materials) -> Result<Self, ObjError> {
+        let mut parser = ObjParser::new(file, materials);
+        for (i, line) in source.lines().enumerate() {
+            parser.line = i + 1;
+            parser.parse_line(line)?;
+        }
+
+        let mut model = parser.model;
+        model.groups.retain(|group| !group.indices.is_empty());
+        Ok(model)
+    }
+
+    /// All groups as one mesh.
+    pub fn to_mesh(&self) -> Mesh<MeshVertex> {
+        let vertices = self.vertices.clone();
+        let indices = self.groups.iter()
+            .flat_map(|group| group.indices.iter().cloned())
+            .collect();
+        Mesh::indexed(vertices, indices)
+    }
+}
+
+/// Parses the `newmtl` and `Kd` statements of MTL source, everything else is ignored.
+pub fn parse_mtl(file: &str, source: &str) -> Result<Materials, ObjError> {
+    let mut materials = Materials::new();
+    let mut current = None;
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { file: file.to_owned(), line: i + 1, message };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("newmtl") => {
+                let name = words.next().ok_or_else(|| error("newmtl without a name".to_owned()))?;
+                materials.insert(name.to_owned(), DEFAULT_COLOR);
+                current = Some(name.to_owned());
+            }
+            Some("Kd") => {
+                let name = current.as_ref().ok_or_else(|| error("Kd before any newmtl".to_owned()))?;
+                let color = parse_floats::<[f32; 3]>(words).map_err(error)?;
+                materials.insert(name.clone(), color);
+            }
+            _ => (),
+        }
+    }
+    Ok(materials)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_owned(), e))
+}
+
+/// Parses between `min` and `max` floats.
+fn parse_float_range<'a>(words: impl Iterator<Item = &'a str>, min: usize, max: usize) -> Result<Vec<f32>, String> {
+    let values = words
+        .map(|word| word.parse().map_err(|_| format!("`{}` is not a number", word)))
+        .collect::<Result<Vec<f32>, _>>()?;
+    if values.len() < min || values.len() > max {
+        return Err(format!("expected {} to {} numbers, found {}", min, max, values.len()));
+    }
+    Ok(values)
+}
+
+/// Parses exactly `N` floats, where `T` is `[f32; N]`.
+fn parse_floats<'a, T>(words: impl Iterator<Item = &'a str>) -> Result<T, String>
+    where T: Default + AsMut<[f32]>
+{
+    let mut values = T::default();
+    let expected = values.as_mut().len();
+    let mut count = 0;
+    for word in words {
+        if count < expected {
+            values.as_mut()[count] = word.parse().map_err(|_| format!("`{}` is not a number", word))?;
+        }
+        count += 1;
+    }
+    if count != expected {
+        return Err(format!("expected {} numbers, found {}", expected, count));
+    }
+    Ok(values)
+}
+
+/// Key identifying a unique vertex: position, uv and normal indices plus the material
+type VertexKey = (usize, Option<usize>, Option<usize>, Option<usize>);
+
+struct ObjParser<'a> {
+    file: &'a str,
+    line: usize,
+    materials: &'a Materials,
+
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    material_names: Vec<String>,
+
+    group_name: String,
+    material: Option<usize>,
+    vertex_indices: HashMap<VertexKey, u32>,
+    model: ObjModel,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(file: &'a str, materials: &'a Materials) -> Self {
+        Self {
+            file,
+            line: 0,
+            materials,
+            positions: vec![],
+            uvs: vec![],
+            normals: vec![],
+            material_names: vec![],
+            group_name: "default".to_owned(),
+            material: None,
+            vertex_indices: HashMap::new(),
+            model: ObjModel::default(),
+        }
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse { file: self.file.to_owned(), line: self.line, message }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), ObjError> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                //Optional w and vertex colors are ignored
+                let position = parse_floats::<[f32; 3]>(words.take(3)).map_err(|e| self.error(e))?;
+                self.positions.push(position);
+            }
+            Some("vt") => {
+                //v defaults to 0 and the optional w is ignored
+                let uv = parse_float_range(words, 1, 3).map_err(|e| self.error(e))?;
+                self.uvs.push([uv[0], uv.get(1).cloned().unwrap_or(0.0)]);
+            }
+            Some("vn") => {
+                let normal = parse_floats::<[f32; 3]>(words).map_err(|e| self.error(e))?;
+                self.normals.push(normal);
+            }
+            Some("f") => self.parse_face(words)?,
+            Some("g") | Some("o") => {
+                self.group_name = words.collect::<Vec<_>>().join(" ");
+                self.start_group();
+            }
+            Some("usemtl") => {
+                let name = words.next().ok_or_else(|| self.error("usemtl without a name".to_owned()))?;
+                let first_use = !self.material_names.iter().any(|material| material == name);
+                if first_use && !self.materials.contains_key(name) {
+                    log::warn!("{}:{}: unknown material `{}`, using the default color", self.file, self.line, name);
+                }
+                self.material = Some(self.material_index(name));
+                self.start_group();
+            }
+            //Smoothing groups, lines, points and the already loaded libraries are ignored
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn material_index(&mut self, name: &str) -> usize {
+        match self.material_names.iter().position(|material| material == name) {
+            Some(index) => index,
+            None => {
+                self.material_names.push(name.to_owned());
+                self.material_names.len() - 1
+            }
+        }
+    }
+
+    /// Faces after this go into a new group unless the current one is still empty.
+    fn start_group(&mut self) {
+        let material = self.material.map(|index| self.material_names[index].clone());
+        match self.model.groups.last_mut() {
+            Some(group) if group.indices.is_empty() => {
+                group.name = self.group_name.clone();
+                group.material = material;
+            }
+            _ => self.model.groups.push(ObjGroup { name: self.group_name.clone(), material, indices: vec![] }),
+        }
+    }
+
+    fn parse_face<'w>(&mut self, words: impl Iterator<Item = &'w str>) -> Result<(), ObjError> {
+        let corners = words
+            .map(|word| self.parse_corner(word))
+            .collect::<Result<Vec<_>, _>>()?;
+        if corners.len() < 3 {
+            return Err(self.error(format!("face has {} vertices, at least 3 are needed", corners.len())));
+        }
+
+        if self.model.groups.is_empty() {
+            self.start_group();
+        }
+
+        //Fan triangulation, which is correct for the convex polygons exporters write
+        for i in 1..corners.len() - 1 {
+            for &corner in &[corners[0], corners[i], corners[i + 1]] {
+                let index = self.vertex_index(corner);
+                self.model.groups.last_mut().unwrap().indices.push(index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices.
+    fn parse_corner(&self, word: &str) -> Result<(usize, Option<usize>, Option<usize>), ObjError> {
+        let mut parts = word.split('/');
+        let position = self.resolve(parts.next(), self.positions.len(), "position", word)?
+            .ok_or_else(|| self.error(format!("face vertex `{}` has no position", word)))?;
+        let uv = self.resolve(parts.next(), self.uvs.len(), "texture coordinate", word)?;
+        let normal = self.resolve(parts.next(), self.normals.len(), "normal", word)?;
+        Ok((position, uv, normal))
+    }
+
+    /// Resolves a 1-based or negative (relative) index, `None` for an empty or absent reference.
+    fn resolve(&self, part: Option<&str>, count: usize, kind: &str, word: &str) -> Result<Option<usize>, ObjError> {
+        let part = match part {
+            Some(part) if !part.is_empty() => part,
+            _ => return Ok(None),
+        };
+        let index: i64 = part.parse()
+            .map_err(|_| self.error(format!("invalid face vertex `{}`", word)))?;
+        let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(format!("{} index {} is out of range, {} defined so far", kind, index, count)));
+        }
+        Ok(Some(resolved as usize))
+    }
+
+    fn vertex_index(&mut self, (position, uv, normal): (usize, Option<usize>, Option<usize>)) -> u32 {
+        let key = (position, uv, normal, self.material);
+        if let Some(&index) = self.vertex_indices.get(&key) {
+            return index;
+        }
+
+        let color = self.material
+            .and_then(|index| self.materials.get(&self.material_names[index]))
+            .cloned()
+            .unwrap_or(DEFAULT_COLOR);
+        let index = self.model.vertices.len() as u32;
+        self.model.vertices.push(MeshVertex {
+            position: self.positions[position],
+            normal: normal.map(|normal| self.normals[normal]).unwrap_or_default(),
+            uv: uv.map(|uv| self.uvs[uv]).unwrap_or_default(),
+            tangent: [0.0; 4],
+            color,
+        });
+        self.vertex_indices.insert(key, index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_file::TempFile;
+
+    const QUAD: &str = "\
+v -1 -1 0
+v 1 -1 0
+v 1 1 0
+v -1 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    fn parse(source: &str) -> Result<ObjModel, ObjError> {
+        ObjModel::parse("test.obj", source, &Materials::new())
+    }
+
+    fn error_line(result: Result<ObjModel, ObjError>) -> usize {
+        match result {
+            Err(ObjError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let model = parse(QUAD).unwrap();
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.groups[0].indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(model.vertices[2].uv, [1.0, 1.0]);
+        assert_eq!(model.vertices[2].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn shared_vertices_are_deduplicated() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf -4 -2 -1\n").unwrap();
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.groups[0].indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn groups_and_materials_split_faces() {
+        let materials = parse_mtl("test.mtl", "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n").unwrap();
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\ng first\nusemtl red\nf 1 2 3\ng second\nusemtl blue\nf 1 2 3\n";
+        let model = ObjModel::parse("test.obj", source, &materials).unwrap();
+
+        let groups: Vec<_> = model.groups.iter()
+            .map(|group| (group.name.as_str(), group.material.as_deref()))
+            .collect();
+        assert_eq!(groups, vec![("first", Some("red")), ("second", Some("blue"))]);
+        //Same positions with different materials are different vertices
+        assert_eq!(model.vertices.len(), 6);
+        assert_eq!(model.vertices[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(model.vertices[3].color, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        assert_eq!(error_line(parse("v 0 0 0\nv 1 x 0\n")), 2);
+        assert_eq!(error_line(parse("v 0 0 0\nv 1 0 0\n\nf 1 2 3\n")), 4);
+        assert_eq!(error_line(parse("v 0 0 0\nv 1 0 0\nf 1 2\n")), 3);
+        assert_eq!(error_line(parse("vn 0 0\n")), 1);
+        assert_eq!(error_line(parse("vt\n")), 1);
+        assert_eq!(error_line(parse("vt 0 0 0 0\n")), 1);
+    }
+
+    #[test]
+    fn texture_coordinates_may_omit_v_or_add_w() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0.5\nvt 0.25 0.75 1\nvt 1 1\nf 1/1 2/2 3/3\n").unwrap();
+        let uvs: Vec<_> = model.vertices.iter().map(|vertex| vertex.uv).collect();
+        assert_eq!(uvs, vec![[0.5, 0.0], [0.25, 0.75], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn missing_materials_use_the_default_color() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl missing\nf 1 2 3\n").unwrap();
+        assert_eq!(model.groups[0].material.as_deref(), Some("missing"));
+        assert_eq!(model.vertices[0].color, DEFAULT_COLOR);
+
+        let file = TempFile::with_contents("missing-mtl", "obj",
+            "mtllib does-not-exist.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl red\nf 1 2 3\n");
+        let model = ObjModel::load(file.path()).unwrap();
+        assert_eq!(model.vertices[2].color, DEFAULT_COLOR);
+    }
+
+    #[test]
+    fn mesh_uses_all_groups() {
+        let mesh = parse(QUAD).unwrap().to_mesh();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices.map(|indices| indices.len()), Some(6));
+    }
+}
@@ -0,0 +1,719 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt;
+
+use vulkano::descriptor::descriptor::{
+    DescriptorBufferDesc,
+    DescriptorDesc,
+    DescriptorDescTy,
+    DescriptorImageDesc,
+    DescriptorImageDescArray,
+    DescriptorImageDescDimensions,
+    ShaderStages,
+};
+use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::format::Format;
+use vulkano::pipeline::shader::{ShaderInterfaceDef, ShaderInterfaceDefEntry};
+
+use crate::shader::ShaderStage;
+
+pub(crate) const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+//Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+/// Deepest chain of types referring to other types, e.g. arrays of arrays, that is accepted
+const MAX_TYPE_DEPTH: usize = 64;
+
+//Decorations
+const BLOCK: u32 = 2;
+const BUFFER_BLOCK: u32 = 3;
+const BUILT_IN: u32 = 11;
+const NON_WRITABLE: u32 = 24;
+const LOCATION: u32 = 30;
+const BINDING: u32 = 33;
+const DESCRIPTOR_SET: u32 = 34;
+
+//Storage classes
+const UNIFORM_CONSTANT: u32 = 0;
+const INPUT: u32 = 1;
+const UNIFORM: u32 = 2;
+const OUTPUT: u32 = 3;
+const PUSH_CONSTANT: u32 = 9;
+const STORAGE_BUFFER: u32 = 12;
+
+//Image dimensions
+const DIM_1D: u32 = 0;
+const DIM_2D: u32 = 1;
+const DIM_3D: u32 = 2;
+const DIM_CUBE: u32 = 3;
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Interface and descriptors of a shader entry point, read from its SPIR-V.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderReflection {
+    pub stage: ShaderStage,
+    /// Sorted by location
+    pub inputs: Vec<InterfaceVariable>,
+    /// Sorted by location
+    pub outputs: Vec<InterfaceVariable>,
+    /// Sorted by set and binding
+    pub descriptors: Vec<DescriptorBinding>,
+}
+
+/// A `layout(location = ...) in` or `out` variable, built-ins are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceVariable {
+    pub name: Option<String>,
+    pub location: u32,
+    /// More than one for matrices and arrays
+    pub location_count: u32,
+    /// Format of each location
+    pub format: Format,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub desc: DescriptorDesc,
+}
+
+impl ShaderReflection {
+    /// Reflects the `main` entry point of `stage`.
+    pub fn new(words: &[u32], stage: ShaderStage) -> Result<Self, ReflectError> {
+        let module = Module::parse(words)?;
+        let entry_point = module.entry_points.iter()
+            .find(|entry_point| entry_point.name == "main" && entry_point.execution_model == execution_model(stage))
+            .ok_or(ReflectError::MissingEntryPoint(stage))?;
+
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        for &id in &entry_point.interface {
+            let variable = module.variables.get(&id).ok_or(ReflectError::Malformed("unknown interface variable"))?;
+            let list = match variable.storage_class {
+                INPUT => &mut inputs,
+                OUTPUT => &mut outputs,
+                _ => continue,
+            };
+            if module.is_built_in(id, variable.pointee) {
+                continue;
+            }
+
+            let name = module.names.get(&id).cloned();
+            let describe = || name.clone().unwrap_or_else(|| format!("%{}", id));
+            let location = module.decoration(id, LOCATION).ok_or_else(|| ReflectError::MissingLocation(describe()))?;
+            let (format, location_count) = module.interface_format(variable.pointee)
+                .ok_or_else(|| ReflectError::UnsupportedType(describe()))?;
+            list.push(InterfaceVariable { name, location, location_count, format });
+        }
+        inputs.sort_by_key(|variable| variable.location);
+        outputs.sort_by_key(|variable| variable.location);
+
+        let stages = match stage {
+            ShaderStage::Vertex => ShaderStages { vertex: true, ..ShaderStages::none() },
+            ShaderStage::Fragment => ShaderStages { fragment: true, ..ShaderStages::none() },
+        };
+        let mut descriptors = vec![];
+        for (&id, variable) in &module.variables {
+            match variable.storage_class {
+                UNIFORM_CONSTANT | UNIFORM | STORAGE_BUFFER => (),
+                PUSH_CONSTANT => return Err(ReflectError::PushConstants),
+                _ => continue,
+            }
+
+            let name = module.names.get(&id).cloned();
+            let describe = || name.clone().unwrap_or_else(|| format!("%{}", id));
+            let (set, binding) = match (module.decoration(id, DESCRIPTOR_SET), module.decoration(id, BINDING)) {
+                (Some(set), Some(binding)) => (set, binding),
+                _ => return Err(ReflectError::MissingBinding(describe())),
+            };
+            let (ty, array_count) = module.descriptor_type(variable.pointee, variable.storage_class, false)
+                .ok_or_else(|| ReflectError::UnsupportedType(describe()))?;
+            let readonly = module.is_readonly(id, variable.pointee, &ty);
+            let desc = DescriptorDesc { ty, array_count, stages, readonly };
+            descriptors.push(DescriptorBinding { set, binding, name, desc });
+        }
+        descriptors.sort_by_key(|descriptor| (descriptor.set, descriptor.binding));
+
+        Ok(Self { stage, inputs, outputs, descriptors })
+    }
+
+    pub fn input_interface(&self) -> ReflectedInterface {
+        ReflectedInterface::new(&self.inputs)
+    }
+
+    pub fn output_interface(&self) -> ReflectedInterface {
+        ReflectedInterface::new(&self.outputs)
+    }
+
+    pub fn layout(&self) -> ReflectedLayout {
+        let mut sets: Vec<Vec<Option<DescriptorDesc>>> = vec![];
+        for descriptor in &self.descriptors {
+            let (set, binding) = (descriptor.set as usize, descriptor.binding as usize);
+            if sets.len() <= set {
+                sets.resize(set + 1, vec![]);
+            }
+            if sets[set].len() <= binding {
+                sets[set].resize(binding + 1, None);
+            }
+            sets[set][binding] = Some(descriptor.desc.clone());
+        }
+        ReflectedLayout { sets }
+    }
+}
+
+fn execution_model(stage: ShaderStage) -> u32 {
+    match stage {
+        ShaderStage::Vertex => 0,
+        ShaderStage::Fragment => 4,
+    }
+}
+
+/// Shader inputs or outputs found by reflection.
+#[derive(Clone, Debug)]
+pub struct ReflectedInterface(Vec<ShaderInterfaceDefEntry>);
+
+impl ReflectedInterface {
+    pub(crate) fn new(variables: &[InterfaceVariable]) -> Self {
+        Self(variables.iter()
+            .map(|variable| ShaderInterfaceDefEntry {
+                location: variable.location..variable.location + variable.location_count,
+                format: variable.format,
+                name: variable.name.clone().map(Cow::Owned),
+            })
+            .collect())
+    }
+}
+
+unsafe impl ShaderInterfaceDef for ReflectedInterface {
+    type Iter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.0.clone().into_iter()
+    }
+}
+
+/// Descriptor sets found by reflection, indexed by set then binding.
+#[derive(Clone, Debug)]
+pub struct ReflectedLayout {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+}
+
+unsafe impl PipelineLayoutDesc for ReflectedLayout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(Vec::len)
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets.get(set)?.get(binding)?.clone()
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        0
+    }
+
+    fn push_constants_range(&self, _num: usize) -> Option<PipelineLayoutDescPcRange> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReflectError {
+    /// Missing or wrong magic number, or an instruction running past the end
+    Malformed(&'static str),
+    /// No `main` entry point for the stage
+    MissingEntryPoint(ShaderStage),
+    MissingLocation(String),
+    MissingBinding(String),
+    UnsupportedType(String),
+    /// The renderer never sets push constants
+    PushConstants,
+}
+
+impl error::Error for ReflectError {}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReflectError::Malformed(reason) => write!(fmt, "invalid SPIR-V: {}", reason),
+            ReflectError::MissingEntryPoint(stage) => write!(fmt, "no {:?} entry point named `main`", stage),
+            ReflectError::MissingLocation(name) => write!(fmt, "`{}` has no location", name),
+            ReflectError::MissingBinding(name) => write!(fmt, "`{}` has no descriptor set or binding", name),
+            ReflectError::UnsupportedType(name) => write!(fmt, "`{}` has a type that is not supported", name),
+            ReflectError::PushConstants => write!(fmt, "push constants are not supported"),
+        }
+    }
+}
+
+/// The types reflection understands, runtime arrays and others are unsupported.
+#[derive(Clone, Debug)]
+enum Type {
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, arrayed: bool, multisampled: bool, sampled: u32 },
+    Sampler,
+    SampledImage { image: u32 },
+    /// `length` is the id of a constant
+    Array { element: u32, length: u32 },
+    Struct { members: u32 },
+}
+
+impl Type {
+    /// The type this one is built from, struct members are not followed.
+    fn inner(&self) -> Option<u32> {
+        match *self {
+            Type::Vector { component, .. } => Some(component),
+            Type::Matrix { column, .. } => Some(column),
+            Type::SampledImage { image } => Some(image),
+            Type::Array { element, .. } => Some(element),
+            _ => None,
+        }
+    }
+}
+
+struct EntryPoint {
+    execution_model: u32,
+    name: String,
+    interface: Vec<u32>,
+}
+
+struct Variable {
+    storage_class: u32,
+    /// Type the variable's pointer type points to
+    pointee: u32,
+}
+
+/// The instructions of a module reflection needs, by result id.
+#[derive(Default)]
+struct Module {
+    entry_points: Vec<EntryPoint>,
+    names: HashMap<u32, String>,
+    decorations: HashMap<(u32, u32), Vec<u32>>,
+    /// Structs with built-in members, such as `gl_PerVertex`
+    built_in_structs: HashSet<u32>,
+    /// Members of each struct decorated `NonWritable`, as in `readonly buffer` blocks
+    non_writable_members: HashMap<u32, HashSet<u32>>,
+    types: HashMap<u32, Type>,
+    /// Pointer type to pointee type
+    pointers: HashMap<u32, u32>,
+    constants: HashMap<u32, u32>,
+    variables: HashMap<u32, Variable>,
+}
+
+impl Module {
+    fn parse(words: &[u32]) -> Result<Self, ReflectError> {
+        if words.len() < HEADER_WORDS || words[0] != SPIRV_MAGIC {
+            return Err(ReflectError::Malformed("missing SPIR-V header"));
+        }
+
+        let mut module = Self::default();
+        let mut rest = &words[HEADER_WORDS..];
+        while let Some(&first) = rest.first() {
+            let (opcode, word_count) = (first & 0xffff, (first >> 16) as usize);
+            if word_count == 0 || word_count > rest.len() {
+                return Err(ReflectError::Malformed("truncated instruction"));
+            }
+            module.add_instruction(opcode, &rest[1..word_count])?;
+            rest = &rest[word_count..];
+        }
+        module.check_type_depth()?;
+        Ok(module)
+    }
+
+    /// Fails for types that refer to themselves, directly or through others, so that following element types
+    /// always ends.
+    fn check_type_depth(&self) -> Result<(), ReflectError> {
+        for &ty in self.types.keys() {
+            let mut ty = ty;
+            let mut depth = 0;
+            while let Some(inner) = self.types.get(&ty).and_then(Type::inner) {
+                depth += 1;
+                if depth > MAX_TYPE_DEPTH {
+                    return Err(ReflectError::Malformed("types refer to themselves or are nested too deeply"));
+                }
+                ty = inner;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_instruction(&mut self, opcode: u32, operands: &[u32]) -> Result<(), ReflectError> {
+        let operand = |index: usize| operands.get(index).copied().ok_or(ReflectError::Malformed("missing operand"));
+        match opcode {
+            OP_NAME => {
+                let (name, _) = parse_string(operands.get(1..).unwrap_or(&[]));
+                self.names.insert(operand(0)?, name);
+            }
+            OP_ENTRY_POINT => {
+                let (name, name_words) = parse_string(operands.get(2..).unwrap_or(&[]));
+                let interface = operands.get(2 + name_words..).unwrap_or(&[]).to_vec();
+                self.entry_points.push(EntryPoint { execution_model: operand(0)?, name, interface });
+            }
+            OP_TYPE_INT => {
+                self.types.insert(operand(0)?, Type::Int { width: operand(1)?, signed: operand(2)? != 0 });
+            }
+            OP_TYPE_FLOAT => { self.types.insert(operand(0)?, Type::Float { width: operand(1)? }); }
+            OP_TYPE_VECTOR => {
+                self.types.insert(operand(0)?, Type::Vector { component: operand(1)?, count: operand(2)? });
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(operand(0)?, Type::Matrix { column: operand(1)?, count: operand(2)? });
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(operand(0)?, Type::Image {
+                    dim: operand(2)?,
+                    arrayed: operand(4)? != 0,
+                    multisampled: operand(5)? != 0,
+                    sampled: operand(6)?,
+                });
+            }
+            OP_TYPE_SAMPLER => { self.types.insert(operand(0)?, Type::Sampler); }
+            OP_TYPE_SAMPLED_IMAGE => { self.types.insert(operand(0)?, Type::SampledImage { image: operand(1)? }); }
+            OP_TYPE_ARRAY => {
+                self.types.insert(operand(0)?, Type::Array { element: operand(1)?, length: operand(2)? });
+            }
+            OP_TYPE_STRUCT => {
+                self.types.insert(operand(0)?, Type::Struct { members: operands.len() as u32 - 1 });
+            }
+            OP_TYPE_POINTER => { self.pointers.insert(operand(0)?, operand(2)?); }
+            OP_CONSTANT => { self.constants.insert(operand(1)?, operand(2)?); }
+            OP_VARIABLE => {
+                let pointee = *self.pointers.get(&operand(0)?).ok_or(ReflectError::Malformed("variable is not a pointer"))?;
+                self.variables.insert(operand(1)?, Variable { storage_class: operand(2)?, pointee });
+            }
+            OP_DECORATE => {
+                self.decorations.insert((operand(0)?, operand(1)?), operands.get(2..).unwrap_or(&[]).to_vec());
+            }
+            OP_MEMBER_DECORATE if operands.get(2) == Some(&BUILT_IN) => { self.built_in_structs.insert(operand(0)?); }
+            OP_MEMBER_DECORATE if operands.get(2) == Some(&NON_WRITABLE) => {
+                self.non_writable_members.entry(operand(0)?).or_default().insert(operand(1)?);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// First literal of a decoration, `None` if the id is not decorated with it.
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&(id, decoration))?.first().copied()
+    }
+
+    fn is_built_in(&self, variable: u32, pointee: u32) -> bool {
+        let mut ty = pointee;
+        while let Some(Type::Array { element, .. }) = self.types.get(&ty) {
+            ty = *element;
+        }
+        self.decorations.contains_key(&(variable, BUILT_IN)) || self.built_in_structs.contains(&ty)
+    }
+
+    /// Format of each location and the number of locations.
+    fn interface_format(&self, ty: u32) -> Option<(Format, u32)> {
+        match self.types.get(&ty)? {
+            Type::Int { .. } | Type::Float { .. } => Some((self.vector_format(ty, 1)?, 1)),
+            Type::Vector { component, count } => Some((self.vector_format(*component, *count)?, 1)),
+            Type::Matrix { column, count } => Some((self.interface_format(*column)?.0, *count)),
+            Type::Array { element, length } => {
+                let (format, count) = self.interface_format(*element)?;
+                Some((format, count.checked_mul(*self.constants.get(length)?)?))
+            }
+            _ => None,
+        }
+    }
+
+    fn vector_format(&self, component: u32, count: u32) -> Option<Format> {
+        use vulkano::format::Format::*;
+
+        let formats = match self.types.get(&component)? {
+            Type::Float { width: 32 } => [R32Sfloat, R32G32Sfloat, R32G32B32Sfloat, R32G32B32A32Sfloat],
+            Type::Float { width: 64 } => [R64Sfloat, R64G64Sfloat, R64G64B64Sfloat, R64G64B64A64Sfloat],
+            Type::Int { width: 32, signed: true } => [R32Sint, R32G32Sint, R32G32B32Sint, R32G32B32A32Sint],
+            Type::Int { width: 32, signed: false } => [R32Uint, R32G32Uint, R32G32B32Uint, R32G32B32A32Uint],
+            _ => return None,
+        };
+        formats.get(count.checked_sub(1)? as usize).copied()
+    }
+
+    /// Whether the shader only reads the descriptor. Storage buffers and images are written unless the variable is
+    /// decorated `NonWritable`, or for buffers every member of the block.
+    fn is_readonly(&self, variable: u32, pointee: u32, ty: &DescriptorDescTy) -> bool {
+        if self.decorations.contains_key(&(variable, NON_WRITABLE)) {
+            return true;
+        }
+        match ty {
+            DescriptorDescTy::Buffer(DescriptorBufferDesc { storage: true, .. }) => {
+                let mut block = pointee;
+                while let Some(Type::Array { element, .. }) = self.types.get(&block) {
+                    block = *element;
+                }
+                match (self.types.get(&block), self.non_writable_members.get(&block)) {
+                    (Some(&Type::Struct { members }), Some(non_writable)) => non_writable.len() as u32 == members,
+                    _ => false,
+                }
+            }
+            DescriptorDescTy::Image(DescriptorImageDesc { sampled: false, .. }) => false,
+            DescriptorDescTy::TexelBuffer { storage: true, .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Descriptor type and array count, following what `vulkano_shaders` generates.
+    fn descriptor_type(&self, ty: u32, storage_class: u32, combined: bool) -> Option<(DescriptorDescTy, u32)> {
+        let desc = match self.types.get(&ty)? {
+            Type::Struct { .. } => {
+                let storage = storage_class == STORAGE_BUFFER || self.decorations.contains_key(&(ty, BUFFER_BLOCK));
+                if !storage && !self.decorations.contains_key(&(ty, BLOCK)) {
+                    return None;
+                }
+                DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage })
+            }
+            &Type::Image { dim, arrayed, multisampled, sampled } => {
+                let array_layers = if arrayed {
+                    DescriptorImageDescArray::Arrayed { max_layers: None }
+                } else {
+                    DescriptorImageDescArray::NonArrayed
+                };
+                let dimensions = match dim {
+                    DIM_1D => DescriptorImageDescDimensions::OneDimensional,
+                    DIM_2D => DescriptorImageDescDimensions::TwoDimensional,
+                    DIM_3D => DescriptorImageDescDimensions::ThreeDimensional,
+                    DIM_CUBE => DescriptorImageDescDimensions::Cube,
+                    DIM_BUFFER => return Some((DescriptorDescTy::TexelBuffer { storage: sampled != 1, format: None }, 1)),
+                    DIM_SUBPASS_DATA => return Some((DescriptorDescTy::InputAttachment { multisampled, array_layers }, 1)),
+                    _ => return None,
+                };
+                let image = DescriptorImageDesc { sampled: sampled == 1, dimensions, format: None, multisampled, array_layers };
+                if combined {
+                    DescriptorDescTy::CombinedImageSampler(image)
+                } else {
+                    DescriptorDescTy::Image(image)
+                }
+            }
+            Type::SampledImage { image } => return self.descriptor_type(*image, storage_class, true),
+            Type::Sampler => DescriptorDescTy::Sampler,
+            Type::Array { element, length } => {
+                //Arrays of arrays are not supported
+                match self.descriptor_type(*element, storage_class, false)? {
+                    (desc, 1) => return Some((desc, *self.constants.get(length)?)),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some((desc, 1))
+    }
+}
+
+/// A nul-terminated UTF-8 literal and the number of words it takes.
+fn parse_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = vec![];
+    for (i, word) in words.iter().enumerate() {
+        for &byte in &word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn string(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect()
+    }
+
+    fn name(id: u32, name: &str) -> Vec<u32> {
+        instruction(OP_NAME, &[vec![id], string(name)].concat())
+    }
+
+    /// A vertex shader whose entry point lists `interface`, with `instructions` after it.
+    fn module(interface: &[u32], instructions: &[Vec<u32>]) -> Vec<u32> {
+        let entry_point = instruction(OP_ENTRY_POINT, &[vec![0, 1], string("main"), interface.to_vec()].concat());
+        [vec![SPIRV_MAGIC, 0x0001_0000, 0, 64, 0], entry_point, instructions.concat()].concat()
+    }
+
+    /// `layout(location = 0) in vec3 position`, `layout(location = 0) out vec3 fragColor`, `gl_Position`, a uniform
+    /// block of one `mat4` at binding 0 and a `sampler2D` at binding 1.
+    fn vertex_module() -> Vec<u32> {
+        let instructions = vec![
+            instruction(OP_ENTRY_POINT, &[vec![0, 1], string("main"), vec![8, 9, 15]].concat()),
+            name(8, "position"),
+            name(9, "fragColor"),
+            name(12, "ubo"),
+            name(23, "texSampler"),
+            instruction(OP_DECORATE, &[8, LOCATION, 0]),
+            instruction(OP_DECORATE, &[9, LOCATION, 0]),
+            instruction(OP_DECORATE, &[10, BLOCK]),
+            instruction(OP_DECORATE, &[12, DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[12, BINDING, 0]),
+            instruction(OP_DECORATE, &[23, DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[23, BINDING, 1]),
+            instruction(OP_MEMBER_DECORATE, &[13, 0, BUILT_IN, 0]),
+            instruction(OP_TYPE_FLOAT, &[2, 32]),
+            instruction(OP_TYPE_VECTOR, &[3, 2, 3]),
+            instruction(OP_TYPE_VECTOR, &[4, 2, 4]),
+            instruction(OP_TYPE_MATRIX, &[5, 4, 4]),
+            instruction(OP_TYPE_POINTER, &[6, INPUT, 3]),
+            instruction(OP_TYPE_POINTER, &[7, OUTPUT, 3]),
+            instruction(OP_VARIABLE, &[6, 8, INPUT]),
+            instruction(OP_VARIABLE, &[7, 9, OUTPUT]),
+            instruction(OP_TYPE_STRUCT, &[10, 5]),
+            instruction(OP_TYPE_POINTER, &[11, UNIFORM, 10]),
+            instruction(OP_VARIABLE, &[11, 12, UNIFORM]),
+            instruction(OP_TYPE_STRUCT, &[13, 4]),
+            instruction(OP_TYPE_POINTER, &[14, OUTPUT, 13]),
+            instruction(OP_VARIABLE, &[14, 15, OUTPUT]),
+            instruction(OP_TYPE_IMAGE, &[20, 2, DIM_2D, 0, 0, 0, 1, 0]),
+            instruction(OP_TYPE_SAMPLED_IMAGE, &[21, 20]),
+            instruction(OP_TYPE_POINTER, &[22, UNIFORM_CONSTANT, 21]),
+            instruction(OP_VARIABLE, &[22, 23, UNIFORM_CONSTANT]),
+        ];
+        [vec![SPIRV_MAGIC, 0x0001_0000, 0, 24, 0], instructions.concat()].concat()
+    }
+
+    #[test]
+    fn interfaces_and_descriptors_are_reflected() {
+        let reflection = ShaderReflection::new(&vertex_module(), ShaderStage::Vertex).unwrap();
+        let vec3 = |name: &str| InterfaceVariable {
+            name: Some(name.to_owned()),
+            location: 0,
+            location_count: 1,
+            format: Format::R32G32B32Sfloat,
+        };
+        assert_eq!(reflection.inputs, vec![vec3("position")]);
+        assert_eq!(reflection.outputs, vec![vec3("fragColor")]);
+
+        let vertex = ShaderStages { vertex: true, ..ShaderStages::none() };
+        let image = DescriptorImageDesc {
+            sampled: true,
+            dimensions: DescriptorImageDescDimensions::TwoDimensional,
+            format: None,
+            multisampled: false,
+            array_layers: DescriptorImageDescArray::NonArrayed,
+        };
+        let descriptors: Vec<_> = reflection.descriptors.iter().map(|d| (d.set, d.binding, d.desc.clone())).collect();
+        assert_eq!(descriptors, vec![
+            (0, 0, DescriptorDesc {
+                ty: DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: false }),
+                array_count: 1,
+                stages: vertex,
+                readonly: true,
+            }),
+            (0, 1, DescriptorDesc {
+                ty: DescriptorDescTy::CombinedImageSampler(image),
+                array_count: 1,
+                stages: vertex,
+                readonly: true,
+            }),
+        ]);
+
+        let layout = reflection.layout();
+        assert_eq!((layout.num_sets(), layout.num_bindings_in_set(0)), (1, Some(2)));
+    }
+
+    #[test]
+    fn missing_stages_and_garbage_are_rejected() {
+        assert_eq!(ShaderReflection::new(&vertex_module(), ShaderStage::Fragment),
+            Err(ReflectError::MissingEntryPoint(ShaderStage::Fragment)));
+        assert!(matches!(ShaderReflection::new(&[1, 2, 3, 4, 5], ShaderStage::Vertex), Err(ReflectError::Malformed(_))));
+
+        let mut truncated = vertex_module();
+        truncated.pop();
+        assert!(matches!(ShaderReflection::new(&truncated, ShaderStage::Vertex), Err(ReflectError::Malformed(_))));
+    }
+
+    #[test]
+    fn oversized_arrays_are_unsupported() {
+        // `in mat4 bones[0xffffffff]` takes more locations than fit in a u32
+        let words = module(&[8], &[
+            name(8, "bones"),
+            instruction(OP_DECORATE, &[8, LOCATION, 0]),
+            instruction(OP_TYPE_FLOAT, &[2, 32]),
+            instruction(OP_TYPE_VECTOR, &[3, 2, 4]),
+            instruction(OP_TYPE_MATRIX, &[4, 3, 4]),
+            instruction(OP_TYPE_INT, &[5, 32, 0]),
+            instruction(OP_CONSTANT, &[5, 6, u32::MAX]),
+            instruction(OP_TYPE_ARRAY, &[7, 4, 6]),
+            instruction(OP_TYPE_POINTER, &[9, INPUT, 7]),
+            instruction(OP_VARIABLE, &[9, 8, INPUT]),
+        ]);
+        assert_eq!(ShaderReflection::new(&words, ShaderStage::Vertex),
+            Err(ReflectError::UnsupportedType("bones".to_owned())));
+    }
+
+    #[test]
+    fn storage_buffers_are_writable_unless_non_writable() {
+        // `readonly buffer` at binding 0 and `buffer` at binding 1, each with one float
+        let words = module(&[], &[
+            instruction(OP_DECORATE, &[10, BLOCK]),
+            instruction(OP_MEMBER_DECORATE, &[10, 0, NON_WRITABLE]),
+            instruction(OP_DECORATE, &[12, DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[12, BINDING, 0]),
+            instruction(OP_DECORATE, &[20, BLOCK]),
+            instruction(OP_DECORATE, &[22, DESCRIPTOR_SET, 0]),
+            instruction(OP_DECORATE, &[22, BINDING, 1]),
+            instruction(OP_TYPE_FLOAT, &[2, 32]),
+            instruction(OP_TYPE_STRUCT, &[10, 2]),
+            instruction(OP_TYPE_POINTER, &[11, STORAGE_BUFFER, 10]),
+            instruction(OP_VARIABLE, &[11, 12, STORAGE_BUFFER]),
+            instruction(OP_TYPE_STRUCT, &[20, 2]),
+            instruction(OP_TYPE_POINTER, &[21, STORAGE_BUFFER, 20]),
+            instruction(OP_VARIABLE, &[21, 22, STORAGE_BUFFER]),
+        ]);
+        let reflection = ShaderReflection::new(&words, ShaderStage::Vertex).unwrap();
+        let readonly: Vec<_> = reflection.descriptors.iter().map(|d| (d.binding, d.desc.readonly)).collect();
+        assert_eq!(readonly, vec![(0, true), (1, false)]);
+    }
+
+    #[test]
+    fn self_referencing_types_are_rejected() {
+        // `%7 = OpTypeArray %7 %6`, reflecting `in` variables or descriptors of it would never end
+        let words = module(&[8], &[
+            name(8, "loop"),
+            instruction(OP_DECORATE, &[8, LOCATION, 0]),
+            instruction(OP_TYPE_INT, &[5, 32, 0]),
+            instruction(OP_CONSTANT, &[5, 6, 2]),
+            instruction(OP_TYPE_ARRAY, &[7, 7, 6]),
+            instruction(OP_TYPE_POINTER, &[9, INPUT, 7]),
+            instruction(OP_VARIABLE, &[9, 8, INPUT]),
+        ]);
+        assert!(matches!(ShaderReflection::new(&words, ShaderStage::Vertex), Err(ReflectError::Malformed(_))));
+    }
+}
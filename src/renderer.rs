@@ -0,0 +1,1671 @@
+use std::sync::Arc;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use vulkano::instance::{
+    Instance, 
+    InstanceExtensions, 
+    ApplicationInfo, 
+    Version, 
+    layers_list,
+    PhysicalDevice,
+};
+use vulkano::instance::debug::DebugCallback;
+use vulkano::device::{Device, DeviceExtensions, Queue, Features};
+use vulkano::swapchain::{
+    Surface,
+    Capabilities,
+    ColorSpace,
+    SupportedPresentModes,
+    PresentMode,
+    Swapchain,
+    CompositeAlpha,
+    FullscreenExclusive,
+    acquire_next_image,
+    SwapchainAcquireFuture,
+    AcquireError,
+};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::{
+    ImageUsage,
+    ImageAccess,
+    ImageViewAccess,
+    ImageCreationError,
+    AttachmentImage,
+    Dimensions,
+    ImmutableImage,
+    ImageLayout,
+    MipmapsCount,
+    StorageImage,
+    swapchain::SwapchainImage,
+};
+use vulkano::sampler::{Filter, Sampler};
+use vulkano::sync::{self, SharingMode, GpuFuture};
+use vulkano::pipeline::{
+    GraphicsPipeline,
+    GraphicsPipelineAbstract,
+    shader::GraphicsEntryPointAbstract,
+    viewport::Viewport,
+};
+use vulkano::framebuffer::{
+    RenderPassAbstract,
+    Subpass,
+    FramebufferAbstract,
+    Framebuffer,
+};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::command_buffer::{
+    AutoCommandBuffer,
+    AutoCommandBufferBuilder,
+    CommandBuffer,
+    DynamicState,
+};
+use vulkano::buffer::{
+    cpu_access::CpuAccessibleBuffer,
+    CpuBufferPool,
+    ImmutableBuffer,
+    BufferUsage,
+    BufferAccess,
+    TypedBufferAccess,
+};
+
+use vulkano_win::VkSurfaceBuild;
+
+use winit::{
+    event_loop::EventLoop,
+    window::{WindowBuilder, Window}, dpi::LogicalSize,
+};
+
+use crate::capture::FrameCapture;
+use crate::config::RendererConfig;
+use crate::draw::{Draw, DrawList, MeshHandle, TextureHandle};
+use crate::error::RendererError;
+use crate::gpu::{self, DeviceScore, GpuInfo};
+use crate::interface;
+use crate::mesh::{GpuMesh, IndexBuffer, Indices, Mesh};
+use crate::obj::ObjModel;
+use crate::scene::Scene;
+use crate::shader::{self, RuntimeShader, ShaderStage, ShaderWatcher};
+use crate::texture::{SamplerSettings, Texture, TextureColorSpace};
+use crate::report::{CapabilityReport, DeviceReport};
+use crate::transform::Transforms;
+use crate::validation::{self, ValidationLog};
+use crate::vertex::{
+    ColorVertex,
+    LayoutVertex,
+    MeshVertex,
+    NormalColorVertex,
+    NormalUvTangentVertex,
+    NormalUvVertex,
+    Vertex,
+    VertexLayout,
+};
+
+/// Color format of the offscreen target used when running headless
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8Unorm;
+
+/// Depth buffer formats in order of preference, `D16Unorm` is supported everywhere
+const DEPTH_FORMATS: [Format; 4] = [Format::D32Sfloat, Format::D32Sfloat_S8Uint, Format::D24Unorm_S8Uint, Format::D16Unorm];
+
+type SwapchainWithImages = (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>);
+type Framebuffers = Vec<Arc<dyn FramebufferAbstract + Send + Sync>>;
+/// The device with its graphics and present queues, which may be the same queue
+type DeviceWithQueues = (Arc<Device>, Arc<Queue>, Arc<Queue>);
+/// Vertex and fragment shader of one layout, loaded from the shader directory
+type RuntimeShaders = (RuntimeShader, RuntimeShader);
+/// Invisible window surface with the event loop it was created on, dropped after the surface
+type ProbeSurface = (Arc<Surface<Window>>, EventLoop<()>);
+
+/// Required device extensions, the swapchain is only needed when presenting to a surface
+fn device_extensions(presenting: bool) -> DeviceExtensions {
+    DeviceExtensions {
+        khr_swapchain: presenting,
+        .. vulkano::device::DeviceExtensions::none()
+    }
+}
+
+/// Queue family indices of a physical device, `-1` when no suitable family was found.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QueueFamilyIndices {
+    pub graphics_family: i32,
+    pub present_family: i32,
+}
+
+impl QueueFamilyIndices {
+    pub fn new() -> Self {
+        Self { graphics_family: -1, present_family: -1 }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.graphics_family >= 0 && self.present_family >= 0
+    }
+}
+
+impl Default for QueueFamilyIndices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(unused)]
+pub struct Renderer {
+    config: RendererConfig,
+
+    instance: Arc<Instance>,
+    debug_callback: Option<DebugCallback>,
+    validation_log: ValidationLog,
+
+    surface: Option<Arc<Surface<Window>>>, //None when rendering headless
+
+    physical_device_index: usize, //Can't store PhysicalDevice directly (lifetime issues)
+    device: Arc<Device>,
+
+    graphics_queue: Arc<Queue>,
+    present_queue: Arc<Queue>,
+
+    swap_chain: Option<Arc<Swapchain<Window>>>,
+    swap_chain_images: Vec<Arc<SwapchainImage<Window>>>,
+    offscreen_image: Option<Arc<AttachmentImage>>,
+    depth_format: Format,
+    samples: u32,
+    depth_image: Arc<AttachmentImage>, //Same size as the color target, recreated with the swapchain
+    msaa_color_image: Option<Arc<AttachmentImage>>, //Resolved into the color target, None without multisampling
+
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    graphics_pipelines: HashMap<VertexLayout, Arc<dyn GraphicsPipelineAbstract + Send + Sync>>, //One per layout in meshes
+    shader_watcher: Option<ShaderWatcher>, //Only when compiling shaders at runtime
+    runtime_shaders: HashMap<VertexLayout, RuntimeShaders>, //Last ones that built a pipeline, reused on resize
+
+    framebuffers: Framebuffers,
+
+    meshes: Vec<GpuMesh>,
+    draw_list: DrawList,
+    default_camera: bool, //The draw list still uses the camera from new(), updated to the aspect ratio on resize
+    uniform_buffers: CpuBufferPool<Transforms>, //Every recorded draw gets its own chunk
+    textures: Vec<Arc<ImmutableImage<Format>>>,
+    white_texture: Arc<ImmutableImage<Format>>, //Bound for draws without a texture
+    sampler: Arc<Sampler>,
+    command_buffers: Option<Vec<Arc<AutoCommandBuffer>>>, //Prebuilt for a static draw list, None to record every frame
+
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    /// Creates a window and a renderer presenting to it.
+    pub fn initialize(config: &RendererConfig) -> Result<(Self, EventLoop<()>), RendererError> {
+        let instance = Self::create_instance(config, false)?;
+        let validation_log = ValidationLog::new();
+        let debug_callback = Self::setup_debug_callback(&instance, config, &validation_log);
+        let (event_loop, surface) = Self::create_surface(&instance, config)?;
+
+        Ok((Self::new(config, instance, debug_callback, validation_log, Some(surface))?, event_loop))
+    }
+
+    /// Renders into an offscreen color attachment, no window or surface is created.
+    pub fn initialize_headless(config: &RendererConfig) -> Result<Self, RendererError> {
+        let instance = Self::create_instance(config, true)?;
+        let validation_log = ValidationLog::new();
+        let debug_callback = Self::setup_debug_callback(&instance, config, &validation_log);
+
+        Self::new(config, instance, debug_callback, validation_log, None)
+    }
+
+    fn new(
+        config: &RendererConfig,
+        instance: Arc<Instance>,
+        debug_callback: Option<DebugCallback>,
+        validation_log: ValidationLog,
+        surface: Option<Arc<Surface<Window>>>,
+    ) -> Result<Self, RendererError> {
+        let physical_device_index = Self::pick_physical_device(&instance, surface.as_ref(), config)?;
+        let (device, graphics_queue, present_queue) = Self::create_logical_device(&instance, surface.as_ref(),
+            physical_device_index, &config.required_features)?;
+
+        let (swap_chain, swap_chain_images) = match &surface {
+            Some(surface) => {
+                let physical_device = PhysicalDevice::from_index(&instance, physical_device_index).unwrap();
+                let (swap_chain, images) = Self::create_swap_chain(config, physical_device, surface, &device,
+                    &graphics_queue, &present_queue, None)?;
+                (Some(swap_chain), images)
+            }
+            None => (None, vec![]),
+        };
+        let offscreen_image = if surface.is_none() {
+            Some(Self::create_offscreen_image(&device, config)?)
+        } else {
+            None
+        };
+
+        let (color_format, extent) = match &swap_chain {
+            Some(swap_chain) => (swap_chain.format(), swap_chain.dimensions()),
+            None => (OFFSCREEN_FORMAT, [config.width, config.height]),
+        };
+        let depth_format = Self::find_depth_format(&device)?;
+        let samples = Self::pick_sample_count(&instance, physical_device_index, config.msaa_samples);
+        let depth_image = Self::create_depth_image(&device, extent, depth_format, samples)?;
+        let msaa_color_image = Self::create_msaa_color_image(&device, extent, color_format, samples)?;
+        let render_pass = Self::create_render_pass(&device, color_format, depth_format, samples)?;
+        let vertex_layout = config.vertex_layout;
+        let mut runtime_shaders = HashMap::new();
+        if let Some(dir) = &config.shader_dir {
+            runtime_shaders.insert(vertex_layout, Self::load_runtime_shaders(&device, dir, vertex_layout)?);
+        }
+        let mut graphics_pipelines = HashMap::new();
+        graphics_pipelines.insert(vertex_layout, Self::create_graphics_pipeline(&device, extent, &render_pass,
+            vertex_layout, runtime_shaders.get(&vertex_layout))?);
+        let shader_watcher = config.shader_dir.as_ref().map(|dir| ShaderWatcher::new(Self::shader_paths(dir)));
+
+        let framebuffers = match &offscreen_image {
+            Some(image) => Self::create_offscreen_framebuffers(image, &depth_image, msaa_color_image.as_ref(), &render_pass)?,
+            None => Self::create_framebuffers(&swap_chain_images, &depth_image, msaa_color_image.as_ref(), &render_pass)?,
+        };
+
+        let (mesh, mesh_texture) = match &config.mesh {
+            Some(path) => Self::load_mesh(path)?,
+            None => (Mesh::triangle().convert(), None),
+        };
+        if let Some(index) = mesh.out_of_range_index() {
+            return Err(RendererError::IndexOutOfRange { index, vertex_count: mesh.vertices.len() });
+        }
+        let meshes = vec![GpuMesh {
+            layout: vertex_layout,
+            vertex_buffer: Self::upload_vertices(&graphics_queue, &mesh.vertices, vertex_layout)?,
+            index_buffer: Self::create_index_buffer(&graphics_queue, mesh.indices.as_ref())?,
+        }];
+        // The 2D layout is given in clip space, 3D layouts need a camera to get the Y flip and their faces the right
+        // way around for culling
+        let default_camera = vertex_layout != VertexLayout::Position2Color;
+        let transforms = if default_camera {
+            Transforms::camera(extent[0] as f32 / extent[1] as f32)
+        } else {
+            Transforms::default()
+        };
+        let mut draw_list = DrawList::single(MeshHandle(0), transforms);
+
+        let uniform_buffers = CpuBufferPool::uniform_buffer(device.clone());
+        let white_texture = Self::create_texture(&graphics_queue, &Texture::solid([255; 4]), TextureColorSpace::Srgb)?;
+        let textures = match (&config.texture, &mesh_texture) {
+            (Some(path), _) =>
+                vec![Self::create_texture(&graphics_queue, &Texture::load(path)?, config.texture_color_space)?],
+            (None, Some(texture)) => vec![Self::create_texture(&graphics_queue, texture, TextureColorSpace::Srgb)?],
+            (None, None) => vec![],
+        };
+        if !textures.is_empty() {
+            draw_list.draws[0].texture = Some(TextureHandle(0));
+        }
+        let sampler = Self::create_sampler(&device, &config.sampler)?;
+
+        let previous_frame_end = Some(Self::create_sync_objects(&device));
+
+        let mut app = Self {
+            config: config.clone(),
+
+            instance,
+            debug_callback,
+            validation_log,
+
+            surface,
+
+            physical_device_index,
+            device,
+
+            graphics_queue,
+            present_queue,
+
+            swap_chain,
+            swap_chain_images,
+            offscreen_image,
+            depth_format,
+            samples,
+            depth_image,
+            msaa_color_image,
+
+            render_pass,
+            graphics_pipelines,
+            shader_watcher,
+            runtime_shaders,
+
+            framebuffers,
+
+            meshes,
+            draw_list,
+            default_camera,
+            uniform_buffers,
+            textures,
+            white_texture,
+            sampler,
+
+            command_buffers: None,
+
+            previous_frame_end,
+            recreate_swapchain: false,
+        };
+
+        app.prebuild_command_buffers()?;
+        app.check_validation()?;
+        Ok(app)
+    }
+
+    fn create_instance(config: &RendererConfig, headless: bool) -> Result<Arc<Instance>, RendererError> {
+        //Listing layers can fail on broken installs, which should not matter unless layers were asked for
+        let available_layers: Vec<_> = if config.enable_validation_layers || !config.extra_layers.is_empty() {
+            layers_list()?.map(|l| l.name().to_owned()).collect()
+        } else {
+            vec![]
+        };
+        let layers = validation::resolve_layers(&available_layers, config.enable_validation_layers, &config.extra_layers);
+        if config.enable_validation_layers && layers.validation_layer.is_none() {
+            if config.validation.strict {
+                return Err(RendererError::ValidationLayerUnavailable);
+            }
+            log::warn!("Validation layers requested, but not available!")
+        }
+        for layer in &layers.missing {
+            log::warn!("Layer {} requested, but not available!", layer);
+        }
+
+        let supported_extensions = InstanceExtensions::supported_by_core()?;
+        log::debug!("Supported extensions: {:?}", supported_extensions);
+
+        let app_info = ApplicationInfo {
+            application_name: Some("Hello triangle".into()),
+            application_version: Some(Version { major: 1, minor: 0, patch: 0}),
+            engine_name: Some("No Engine".into()),
+            engine_version: Some(Version { major: 1, minor: 0, patch: 0}),
+        };
+
+        let required_extensions = Self::get_required_extensions(config, headless);
+
+        let instance = Instance::new(Some(&app_info), &required_extensions, layers.enabled.iter().map(String::as_str))?;
+        if layers.enabled.is_empty() {
+            log::info!("No instance layers enabled");
+        } else {
+            log::info!("Enabled instance layers: {}", layers.enabled.join(", "));
+        }
+        Ok(instance)
+    }
+
+    fn get_required_extensions(config: &RendererConfig, headless: bool) -> InstanceExtensions {
+        let mut extensions = if headless {
+            InstanceExtensions::none()
+        } else {
+            vulkano_win::required_extensions()
+        };
+        if config.enable_validation_layers {
+            extensions.ext_debug_utils = true;
+        }
+
+        extensions
+    }
+
+    fn create_surface(
+        instance: &Arc<Instance>,
+        config: &RendererConfig,
+    ) -> Result<(EventLoop<()>, Arc<Surface<Window>>), RendererError> {
+        let event_loop = EventLoop::new();
+        let surface = WindowBuilder::new()
+            .with_title(config.window_title.as_str())
+            .with_inner_size(LogicalSize::new(f64::from(config.width), f64::from(config.height)))
+            .build_vk_surface(&event_loop, instance.clone())?;
+        Ok((event_loop, surface))
+    }
+
+    fn setup_debug_callback(
+        instance: &Arc<Instance>,
+        config: &RendererConfig,
+        validation_log: &ValidationLog,
+    ) -> Option<DebugCallback> {
+        if !config.enable_validation_layers {
+            return None;
+        }
+
+        let settings = &config.validation;
+        DebugCallback::new(
+            instance,
+            settings.min_severity.enabled(),
+            settings.message_types(),
+            validation::log_callback(settings, validation_log),
+        ).ok()
+    }
+
+    /// Describes every physical device and whether the renderer could use it with `config`.
+    ///
+    /// Unless `config.headless` is set, an invisible window is created so presentation support can be checked.
+    pub fn list_gpus(config: &RendererConfig) -> Result<Vec<GpuInfo>, RendererError> {
+        let instance = Self::create_instance(config, config.headless)?;
+        let probe = Self::probe_surface(&instance, config)?;
+        let surface = probe.as_ref().map(|(surface, _)| surface);
+
+        Ok(PhysicalDevice::enumerate(&instance)
+            .map(|device| {
+                let problems = Self::device_problems(surface, &device, &config.required_features);
+                GpuInfo::new(&device, surface, problems)
+            })
+            .collect())
+    }
+
+    /// Collects the instance extensions, layers and per-device capabilities, like `list_gpus` does.
+    pub fn capability_report(config: &RendererConfig) -> Result<CapabilityReport, RendererError> {
+        let instance = Self::create_instance(config, config.headless)?;
+        let probe = Self::probe_surface(&instance, config)?;
+        let surface = probe.as_ref().map(|(surface, _)| surface);
+
+        let devices = PhysicalDevice::enumerate(&instance)
+            .map(|device| {
+                let problems = Self::device_problems(surface, &device, &config.required_features);
+                let info = GpuInfo::new(&device, surface, problems);
+                DeviceReport::new(&device, surface, info)
+            })
+            .collect();
+        Ok(CapabilityReport::new(&InstanceExtensions::supported_by_core()?, layers_list()?, devices))
+    }
+
+    /// Invisible window surface used to check presentation support, `None` when headless. The event loop is only
+    /// created for the window, winit cannot create one without a display.
+    fn probe_surface(instance: &Arc<Instance>, config: &RendererConfig) -> Result<Option<ProbeSurface>, RendererError> {
+        if config.headless {
+            return Ok(None);
+        }
+        let event_loop = EventLoop::new();
+        let surface = WindowBuilder::new()
+            .with_visible(false)
+            .build_vk_surface(&event_loop, instance.clone())?;
+        Ok(Some((surface, event_loop)))
+    }
+
+    fn pick_physical_device(
+        instance: &Arc<Instance>,
+        surface: Option<&Arc<Surface<Window>>>,
+        config: &RendererConfig,
+    ) -> Result<usize, RendererError> {
+        let selector = match &config.gpu {
+            Some(selector) => selector,
+            None => return Self::rank_physical_devices(instance, surface, &config.required_features)
+                .first()
+                .map(|(info, _)| info.index)
+                .ok_or(RendererError::NoSuitableDevice),
+        };
+
+        let device = PhysicalDevice::enumerate(instance)
+            .find(|device| selector.matches(device))
+            .ok_or_else(|| RendererError::NoMatchingDevice(selector.clone()))?;
+        let problems = Self::device_problems(surface, &device, &config.required_features);
+        if !problems.is_empty() {
+            return Err(RendererError::UnsuitableDevice { name: device.name(), problems });
+        }
+        Ok(device.index())
+    }
+
+    /// Suitable devices with their scores, best first. Equal ranks keep the enumeration order.
+    fn rank_physical_devices(
+        instance: &Arc<Instance>,
+        surface: Option<&Arc<Surface<Window>>>,
+        required_features: &Features,
+    ) -> Vec<(GpuInfo, DeviceScore)> {
+        let mut ranked: Vec<_> = PhysicalDevice::enumerate(instance)
+            .map(|device| GpuInfo::new(&device, surface, Self::device_problems(surface, &device, required_features)))
+            .filter_map(|info| info.score().map(|score| (info, score)))
+            .collect();
+        ranked.sort_by_key(|(_, score)| Reverse(score.rank()));
+        ranked
+    }
+
+    /// Reasons why `device` cannot be used, empty if it is suitable.
+    fn device_problems(
+        surface: Option<&Arc<Surface<Window>>>,
+        device: &PhysicalDevice,
+        required_features: &Features,
+    ) -> Vec<String> {
+        let mut problems = vec![];
+
+        let indices = Self::find_queue_families(surface, device);
+        if indices.graphics_family < 0 {
+            problems.push("no graphics queue family".to_owned());
+        }
+        if indices.present_family < 0 {
+            problems.push("no queue family can present to the surface".to_owned());
+        }
+
+        if !device.supported_features().superset_of(required_features) {
+            problems.push("missing required features".to_owned());
+        }
+
+        let extensions_supported = Self::check_device_extension_support(device, surface.is_some());
+        if !extensions_supported {
+            problems.push(format!("missing device extensions {:?}", device_extensions(surface.is_some())));
+        }
+
+        match surface {
+            Some(surface) if extensions_supported => {
+                match surface.capabilities(*device) {
+                    Ok(capabilities) => {
+                        if capabilities.supported_formats.is_empty() {
+                            problems.push("surface supports no formats".to_owned());
+                        }
+                        if capabilities.present_modes.iter().next().is_none() {
+                            problems.push("surface supports no present modes".to_owned());
+                        }
+                    }
+                    Err(e) => problems.push(format!("failed to get surface capabilities: {}", e)),
+                }
+            }
+            _ => (), //Nothing to present to when headless
+        }
+
+        problems
+    }
+
+    fn check_device_extension_support(device: &PhysicalDevice, presenting: bool) -> bool {
+        let available_extensions = DeviceExtensions::supported_by_device(*device);
+        let device_extensions = device_extensions(presenting);
+        available_extensions.intersection(&device_extensions) == device_extensions
+    }
+
+    fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace) {
+        *available_formats.iter()
+            .find(|(format, color_space)|
+                *format == Format::B8G8R8A8Unorm && *color_space == ColorSpace::SrgbNonLinear
+            )
+            .unwrap_or_else(|| &available_formats[0])
+    }
+
+    fn choose_swap_present_mode(available_present_modes: SupportedPresentModes, config: &RendererConfig) -> PresentMode {
+        if let Some(present_mode) = config.present_mode.and_then(|mode| mode.select(available_present_modes)) {
+            present_mode
+        } else if available_present_modes.mailbox {
+            PresentMode::Mailbox
+        } else if available_present_modes.immediate {
+            PresentMode::Immediate
+        } else {
+            PresentMode::Fifo
+        }
+    }
+
+    fn choose_swap_extent(capabilities: &Capabilities, config: &RendererConfig) -> [u32; 2] {
+        if let Some(current_extent) = capabilities.current_extent {
+            return current_extent
+        } else {
+            let mut actual_extent = [config.width, config.height];
+            actual_extent[0] = capabilities.min_image_extent[0]
+                .max(capabilities.max_image_extent[0].min(actual_extent[0]));
+            actual_extent[1] = capabilities.min_image_extent[1]
+                .max(capabilities.max_image_extent[1].min(actual_extent[1]));
+            actual_extent
+        }
+    }
+
+    fn create_swap_chain(
+        config: &RendererConfig,
+        physical_device: PhysicalDevice,
+        surface: &Arc<Surface<Window>>,
+        device: &Arc<Device>,
+        graphics_queue: &Arc<Queue>,
+        present_queue: &Arc<Queue>,
+        old_swapchain: Option<Arc<Swapchain<Window>>>,
+    ) -> Result<SwapchainWithImages, RendererError> {
+        let capabilities = surface.capabilities(physical_device)?;
+
+        let surface_format = Self::choose_swap_surface_format(&capabilities.supported_formats);
+        let present_mode = Self::choose_swap_present_mode(capabilities.present_modes, config);
+        let extent = Self::choose_swap_extent(&capabilities, config);
+
+        let mut image_count = capabilities.min_image_count + 1;
+        if capabilities.max_image_count.is_some() && image_count > capabilities.max_image_count.unwrap() {
+            image_count = capabilities.max_image_count.unwrap();
+        }
+
+        let image_usage = ImageUsage {
+            color_attachment: true,
+            transfer_source: capabilities.supported_usage_flags.transfer_source, //Needed for screenshots
+            .. ImageUsage::none()
+        };
+
+        let indices = Self::find_queue_families(Some(surface), &physical_device);
+
+        let sharing: SharingMode = if indices.graphics_family != indices.present_family {
+            vec![graphics_queue, present_queue].as_slice().into()
+        } else {
+            graphics_queue.into()
+        };
+
+        if old_swapchain.is_none() {
+            let (swap_chain, images) = Swapchain::new(
+                device.clone(),
+                surface.clone(),
+                image_count,
+                surface_format.0,
+                extent,
+                1,
+                image_usage,
+                sharing,
+                capabilities.current_transform,
+                CompositeAlpha::Opaque,
+                present_mode,
+                FullscreenExclusive::Default,
+                true, //clipped
+                ColorSpace::SrgbNonLinear,
+            )?;
+
+            Ok((swap_chain, images))
+        } else {
+            let (swap_chain, images) = Swapchain::with_old_swapchain(
+                device.clone(),
+                surface.clone(),
+                image_count,
+                surface_format.0,
+                extent,
+                1,
+                image_usage,
+                sharing,
+                capabilities.current_transform,
+                CompositeAlpha::Opaque,
+                present_mode,
+                FullscreenExclusive::Default,
+                true, //clipped
+                ColorSpace::SrgbNonLinear,
+                old_swapchain.unwrap(),
+            )?;
+
+            Ok((swap_chain, images))
+        }
+    }
+
+    /// Multisampled passes render into `msaa_color`, which is resolved into `color` at the end of the pass.
+    fn create_render_pass(
+        device: &Arc<Device>,
+        color_format: Format,
+        depth_format: Format,
+        samples: u32,
+    ) -> Result<Arc<dyn RenderPassAbstract + Send + Sync>, RendererError> {
+        if samples > 1 {
+            return Ok(Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    msaa_color: {
+                        load: Clear,
+                        store: DontCare,
+                        format: color_format,
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: DontCare,
+                        format: depth_format,
+                        samples: samples,
+                    }
+                },
+                pass: {
+                    color: [msaa_color],
+                    depth_stencil: {depth},
+                    resolve: [color]
+                }
+            )?));
+        }
+
+        Ok(Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: color_format,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: depth_format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth}
+            }
+        )?))
+    }
+
+    /// First of `DEPTH_FORMATS` the device can use as a depth attachment.
+    fn find_depth_format(device: &Arc<Device>) -> Result<Format, RendererError> {
+        //vulkano has no format properties query, creating a tiny image checks the same features
+        for &format in DEPTH_FORMATS.iter() {
+            match AttachmentImage::transient(device.clone(), [1, 1], format) {
+                Ok(_) => return Ok(format),
+                Err(ImageCreationError::FormatNotSupported) | Err(ImageCreationError::UnsupportedUsage) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(ImageCreationError::FormatNotSupported.into())
+    }
+
+    /// Highest sample count up to `requested` the device supports for both color and depth attachments.
+    fn pick_sample_count(instance: &Arc<Instance>, physical_device_index: usize, requested: u32) -> u32 {
+        let limits = PhysicalDevice::from_index(instance, physical_device_index).unwrap().limits();
+        let supported = limits.framebuffer_color_sample_counts() & limits.framebuffer_depth_sample_counts();
+        let samples = gpu::clamp_sample_count(requested, supported);
+        if samples < requested {
+            log::warn!("{}x MSAA is not supported, using {}x", requested, samples);
+        }
+        samples
+    }
+
+    fn create_depth_image(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        depth_format: Format,
+        samples: u32,
+    ) -> Result<Arc<AttachmentImage>, RendererError> {
+        Ok(AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, depth_format)?)
+    }
+
+    /// `None` when not multisampling, the color target is rendered to directly.
+    fn create_msaa_color_image(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        color_format: Format,
+        samples: u32,
+    ) -> Result<Option<Arc<AttachmentImage>>, RendererError> {
+        if samples == 1 {
+            return Ok(None);
+        }
+        Ok(Some(AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, color_format)?))
+    }
+
+    /// Vertex and fragment shader files of a layout, in `src/assets/shaders` or `RendererConfig::shader_dir`.
+    fn shader_files(vertex_layout: VertexLayout) -> (&'static str, &'static str) {
+        match vertex_layout {
+            VertexLayout::Position2Color => ("vert_shader.vert", "frag_shader.frag"),
+            VertexLayout::Position3Color => ("vert_position3_color.vert", "frag_shader.frag"),
+            VertexLayout::Position3NormalColor => ("vert_position3_normal_color.vert", "frag_shader.frag"),
+            VertexLayout::Position3NormalUv => ("vert_position3_normal_uv.vert", "frag_texture.frag"),
+            VertexLayout::Position3NormalUvTangent => ("vert_position3_normal_uv_tangent.vert", "frag_texture.frag"),
+        }
+    }
+
+    /// Every shader file any layout uses, each once, in both its GLSL and precompiled form.
+    fn shader_paths(shader_dir: &Path) -> Vec<PathBuf> {
+        let mut files = VertexLayout::ALL.iter()
+            .flat_map(|&layout| {
+                let (vertex_file, fragment_file) = Self::shader_files(layout);
+                vec![vertex_file, fragment_file]
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+        files.into_iter()
+            .flat_map(|file| vec![shader_dir.join(file), shader_dir.join(format!("{}.spv", file))])
+            .collect()
+    }
+
+    /// Loads the layout's shaders from `shader_dir`, preferring precompiled SPIR-V over compiling GLSL.
+    fn load_runtime_shaders(
+        device: &Arc<Device>,
+        shader_dir: &Path,
+        vertex_layout: VertexLayout,
+    ) -> Result<(RuntimeShader, RuntimeShader), RendererError> {
+        let (vertex_file, fragment_file) = Self::shader_files(vertex_layout);
+        Ok((
+            RuntimeShader::load(device, &shader::shader_path(shader_dir, vertex_file), ShaderStage::Vertex)?,
+            RuntimeShader::load(device, &shader::shader_path(shader_dir, fragment_file), ShaderStage::Fragment)?,
+        ))
+    }
+
+    /// Uses the shaders built into the binary, or the ones loaded from the shader directory when given.
+    fn create_graphics_pipeline(
+        device: &Arc<Device>, 
+        swap_chain_extent: [u32; 2], 
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+        vertex_layout: VertexLayout,
+        runtime_shaders: Option<&RuntimeShaders>,
+    ) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, RendererError> {
+        mod vertex_shader {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                path: "src/assets/shaders/vert_shader.vert"
+            }
+        }
+
+        mod position3_color_shader {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                path: "src/assets/shaders/vert_position3_color.vert"
+            }
+        }
+
+        mod position3_normal_color_shader {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                path: "src/assets/shaders/vert_position3_normal_color.vert"
+            }
+        }
+
+        mod position3_normal_uv_shader {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                path: "src/assets/shaders/vert_position3_normal_uv.vert"
+            }
+        }
+
+        mod position3_normal_uv_tangent_shader {
+            vulkano_shaders::shader! {
+                ty: "vertex",
+                path: "src/assets/shaders/vert_position3_normal_uv_tangent.vert"
+            }
+        }
+
+        mod fragment_shader {
+            vulkano_shaders::shader! {
+                ty: "fragment",
+                path: "src/assets/shaders/frag_shader.frag"
+            }
+        }
+
+        mod texture_fragment_shader {
+            vulkano_shaders::shader! {
+                ty: "fragment",
+                path: "src/assets/shaders/frag_texture.frag"
+            }
+        }
+
+        let dimensions = [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32];
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions,
+            depth_range: 0.0 .. 1.0,
+        };
+
+        //Every layout has its own vertex shader, all of them output fragColor and the textured ones fragUv as well.
+        //Runtime shaders are described by the interfaces reflected from their SPIR-V, built-in ones by the types
+        //generated with them. Either way the interfaces are checked first, vulkano would only report the first
+        //mismatch without naming it. Runtime shaders' descriptors are checked against what create_descriptor_set
+        //binds as well, the built-in ones are written for it.
+        macro_rules! build_pipeline {
+            ($vertex:ty, $shader:ident, $fragment_shader:ident) => {{
+                match runtime_shaders {
+                    Some((vertex, fragment)) => {
+                        interface::check_descriptors::<$vertex>(vertex.descriptors(), fragment.descriptors(),
+                            vertex_layout.is_textured())?;
+                        build_pipeline!(@entry_points $vertex, vertex.entry_point(), fragment.entry_point())
+                    }
+                    None => {
+                        let vert_shader_module = $shader::Shader::load(device.clone())?;
+                        let frag_shader_module = $fragment_shader::Shader::load(device.clone())?;
+                        build_pipeline!(@entry_points $vertex, vert_shader_module.main_entry_point(),
+                            frag_shader_module.main_entry_point())
+                    }
+                }
+            }};
+            (@entry_points $vertex:ty, $vertex_entry_point:expr, $fragment_entry_point:expr) => {{
+                let vertex_entry_point = $vertex_entry_point;
+                let fragment_entry_point = $fragment_entry_point;
+                interface::check_pipeline_interfaces::<$vertex, _, _, _>(
+                    vertex_entry_point.input(),
+                    vertex_entry_point.output(),
+                    fragment_entry_point.input(),
+                )?;
+
+                let builder = GraphicsPipeline::start()
+                    .vertex_input_single_buffer::<$vertex>()
+                    .vertex_shader(vertex_entry_point, ())
+                    .triangle_list()
+                    .primitive_restart(false)
+                    .viewports(vec![viewport]) //NOTE: also sets scissor to cover whole viewport
+                    .fragment_shader(fragment_entry_point, ())
+                    .depth_clamp(false)
+                    .polygon_mode_fill() //= default
+                    .line_width(1.0) // = default
+                    .depth_stencil_simple_depth()
+                    .cull_mode_back();
+                //The tutorial's 2D triangle is clockwise in clip space, 3D models are counter-clockwise in world
+                //space and stay so through the Y flip of the projection
+                let builder = if vertex_layout == VertexLayout::Position2Color {
+                    builder.front_face_clockwise()
+                } else {
+                    builder.front_face_counter_clockwise()
+                };
+                let pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync> = Arc::new(builder
+                    .blend_pass_through()
+                    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                    .build(device.clone())?
+                );
+                pipeline
+            }};
+        }
+
+        Ok(match vertex_layout {
+            VertexLayout::Position2Color => build_pipeline!(Vertex, vertex_shader, fragment_shader),
+            VertexLayout::Position3Color => build_pipeline!(ColorVertex, position3_color_shader, fragment_shader),
+            VertexLayout::Position3NormalColor =>
+                build_pipeline!(NormalColorVertex, position3_normal_color_shader, fragment_shader),
+            VertexLayout::Position3NormalUv =>
+                build_pipeline!(NormalUvVertex, position3_normal_uv_shader, texture_fragment_shader),
+            VertexLayout::Position3NormalUvTangent =>
+                build_pipeline!(NormalUvTangentVertex, position3_normal_uv_tangent_shader, texture_fragment_shader),
+        })
+    }
+
+    fn create_framebuffers(
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        depth_image: &Arc<AttachmentImage>,
+        msaa_color_image: Option<&Arc<AttachmentImage>>,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>
+    ) -> Result<Framebuffers, RendererError> {
+        swap_chain_images.iter()
+            .map(|image| Self::create_framebuffer(image.clone(), depth_image, msaa_color_image, render_pass))
+            .collect()
+    }
+
+    /// Attachments are added in the order `create_render_pass` declares them.
+    fn create_framebuffer<I>(
+        image: I,
+        depth_image: &Arc<AttachmentImage>,
+        msaa_color_image: Option<&Arc<AttachmentImage>>,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>
+    ) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, RendererError>
+        where I: ImageViewAccess + Send + Sync + 'static
+    {
+        Ok(match msaa_color_image {
+            Some(msaa_color_image) => Arc::new(Framebuffer::start(render_pass.clone())
+                .add(msaa_color_image.clone())?
+                .add(image)?
+                .add(depth_image.clone())?
+                .build()?),
+            None => Arc::new(Framebuffer::start(render_pass.clone())
+                .add(image)?
+                .add(depth_image.clone())?
+                .build()?),
+        })
+    }
+
+    fn create_offscreen_image(device: &Arc<Device>, config: &RendererConfig) -> Result<Arc<AttachmentImage>, RendererError> {
+        let usage = ImageUsage {
+            transfer_source: true,
+            .. ImageUsage::none()
+        };
+        Ok(AttachmentImage::with_usage(device.clone(), [config.width, config.height], OFFSCREEN_FORMAT, usage)?)
+    }
+
+    fn create_offscreen_framebuffers(
+        image: &Arc<AttachmentImage>,
+        depth_image: &Arc<AttachmentImage>,
+        msaa_color_image: Option<&Arc<AttachmentImage>>,
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>
+    ) -> Result<Framebuffers, RendererError> {
+        Ok(vec![Self::create_framebuffer(image.clone(), depth_image, msaa_color_image, render_pass)?])
+    }
+
+    fn create_vertex_buffer<V: LayoutVertex>(
+        graphics_queue: &Arc<Queue>,
+        vertices: &[V],
+    ) -> Result<Arc<dyn BufferAccess + Send + Sync>, RendererError> {
+        let (buffer, future) = ImmutableBuffer::from_iter(
+            vertices.iter().cloned(), BufferUsage::vertex_buffer(),
+            graphics_queue.clone())?;
+        future.flush()?;
+        Ok(buffer)
+    }
+
+    /// Converts loaded vertices to `vertex_layout` before uploading them.
+    fn upload_vertices(
+        graphics_queue: &Arc<Queue>,
+        vertices: &[MeshVertex],
+        vertex_layout: VertexLayout,
+    ) -> Result<Arc<dyn BufferAccess + Send + Sync>, RendererError> {
+        fn convert<V: LayoutVertex>(vertices: &[MeshVertex]) -> Vec<V> {
+            vertices.iter().map(|&vertex| V::from(vertex)).collect()
+        }
+
+        match vertex_layout {
+            VertexLayout::Position2Color => Self::create_vertex_buffer::<Vertex>(graphics_queue, &convert(vertices)),
+            VertexLayout::Position3Color => Self::create_vertex_buffer::<ColorVertex>(graphics_queue, &convert(vertices)),
+            VertexLayout::Position3NormalColor =>
+                Self::create_vertex_buffer::<NormalColorVertex>(graphics_queue, &convert(vertices)),
+            VertexLayout::Position3NormalUv =>
+                Self::create_vertex_buffer::<NormalUvVertex>(graphics_queue, &convert(vertices)),
+            VertexLayout::Position3NormalUvTangent =>
+                Self::create_vertex_buffer::<NormalUvTangentVertex>(graphics_queue, &convert(vertices)),
+        }
+    }
+
+    /// Loads a glTF/GLB scene or an OBJ model, depending on the extension, with the scene's base color texture.
+    fn load_mesh(path: &Path) -> Result<(Mesh<MeshVertex>, Option<Texture>), RendererError> {
+        let extension = path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "gltf" | "glb" => {
+                let scene = Scene::load(path)?;
+                Ok((scene.to_mesh(), scene.base_color_texture().cloned()))
+            }
+            _ => Ok((ObjModel::load(path)?.to_mesh(), None)),
+        }
+    }
+
+    fn create_index_buffer(
+        graphics_queue: &Arc<Queue>,
+        indices: Option<&Indices>,
+    ) -> Result<Option<IndexBuffer>, RendererError> {
+        let index_buffer = match indices {
+            Some(Indices::U16(indices)) => {
+                let (buffer, future) = ImmutableBuffer::from_iter(
+                    indices.iter().cloned(), BufferUsage::index_buffer(), graphics_queue.clone())?;
+                future.flush()?;
+                Some(IndexBuffer::U16(buffer))
+            }
+            Some(Indices::U32(indices)) => {
+                let (buffer, future) = ImmutableBuffer::from_iter(
+                    indices.iter().cloned(), BufferUsage::index_buffer(), graphics_queue.clone())?;
+                future.flush()?;
+                Some(IndexBuffer::U32(buffer))
+            }
+            None => None,
+        };
+        Ok(index_buffer)
+    }
+
+    /// Uploads a mesh for use in draw lists, building a pipeline for its vertex layout if there is none yet.
+    pub fn add_mesh<V: LayoutVertex>(&mut self, mesh: &Mesh<V>) -> Result<MeshHandle, RendererError> {
+        if let Some(index) = mesh.out_of_range_index() {
+            return Err(RendererError::IndexOutOfRange { index, vertex_count: mesh.vertices.len() });
+        }
+
+        if !self.graphics_pipelines.contains_key(&V::LAYOUT) {
+            if let Some(dir) = &self.config.shader_dir {
+                let shaders = Self::load_runtime_shaders(&self.device, dir, V::LAYOUT)?;
+                self.runtime_shaders.insert(V::LAYOUT, shaders);
+            }
+            let (_, extent) = self.color_target();
+            let pipeline = Self::create_graphics_pipeline(&self.device, extent, &self.render_pass, V::LAYOUT,
+                self.runtime_shaders.get(&V::LAYOUT))?;
+            self.graphics_pipelines.insert(V::LAYOUT, pipeline);
+        }
+        self.meshes.push(GpuMesh {
+            layout: V::LAYOUT,
+            vertex_buffer: Self::create_vertex_buffer(&self.graphics_queue, &mesh.vertices)?,
+            index_buffer: Self::create_index_buffer(&self.graphics_queue, mesh.indices.as_ref())?,
+        });
+        Ok(MeshHandle(self.meshes.len() - 1))
+    }
+
+    /// Uploads an image for use in draw lists, `color_space` decides whether it is sampled as sRGB or linear data.
+    pub fn add_texture(&mut self, texture: &Texture, color_space: TextureColorSpace) -> Result<TextureHandle, RendererError> {
+        self.textures.push(Self::create_texture(&self.graphics_queue, texture, color_space)?);
+        Ok(TextureHandle(self.textures.len() - 1))
+    }
+
+    /// Uploads the texture with a full mip chain, blitted down on the GPU when the format allows linear blits and
+    /// computed on the CPU otherwise.
+    fn create_texture(
+        graphics_queue: &Arc<Queue>,
+        texture: &Texture,
+        color_space: TextureColorSpace,
+    ) -> Result<Arc<ImmutableImage<Format>>, RendererError> {
+        let device = graphics_queue.device();
+        let dimensions = Dimensions::Dim2d { width: texture.width, height: texture.height };
+        let usage = ImageUsage { transfer_destination: true, sampled: true, ..ImageUsage::none() };
+        let (image, init) = ImmutableImage::uninitialized(device.clone(), dimensions, color_space.format(),
+            MipmapsCount::Specific(texture.mip_levels()), usage, ImageLayout::ShaderReadOnlyOptimal,
+            Some(graphics_queue.family()))?;
+        let init = Arc::new(init);
+
+        let format_image = ImageAccess::inner(&image).image;
+        let blittable = format_image.supports_blit_source()
+            && format_image.supports_blit_destination()
+            && format_image.supports_linear_filtering();
+
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), graphics_queue.family())?;
+        let builder = if blittable {
+            Self::blit_mipmaps(builder, graphics_queue, texture, color_space.format(), &init)?
+        } else {
+            log::debug!("{:?} can't be blitted with linear filtering, generating mipmaps on the CPU", color_space.format());
+            Self::upload_mipmaps(builder, graphics_queue, texture, color_space, &init)?
+        };
+        builder.build()?
+            .execute(graphics_queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        Ok(image)
+    }
+
+    /// Records the upload of the full resolution image and halves it level by level. Vulkano tracks access per image
+    /// rather than per mip level, so each level is blitted into a staging image of its own and copied on from there.
+    fn blit_mipmaps(
+        builder: AutoCommandBufferBuilder,
+        graphics_queue: &Arc<Queue>,
+        texture: &Texture,
+        format: Format,
+        destination: &Arc<impl ImageAccess + Send + Sync + 'static>,
+    ) -> Result<AutoCommandBufferBuilder, RendererError> {
+        let device = graphics_queue.device();
+        let usage = ImageUsage { transfer_source: true, transfer_destination: true, ..ImageUsage::none() };
+        let staging_image = |width: u32, height: u32| StorageImage::with_usage(
+            device.clone(), Dimensions::Dim2d { width, height }, format, usage, Some(graphics_queue.family()));
+
+        let pixels = CpuAccessibleBuffer::from_iter(
+            device.clone(), BufferUsage::transfer_source(), false, texture.pixels.iter().cloned())?;
+        let mut previous = staging_image(texture.width, texture.height)?;
+        let mut builder = builder
+            .copy_buffer_to_image_dimensions(pixels.clone(), destination.clone(),
+                [0, 0, 0], [texture.width, texture.height, 1], 0, 1, 0)?
+            .copy_buffer_to_image(pixels, previous.clone())?;
+
+        let (mut width, mut height) = (texture.width, texture.height);
+        for level in 1..texture.mip_levels() {
+            let (source_width, source_height) = (width, height);
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            let current = staging_image(width, height)?;
+            let size = [width as i32, height as i32, 1];
+            builder = builder
+                .blit_image(previous, [0, 0, 0], [source_width as i32, source_height as i32, 1], 0, 0,
+                    current.clone(), [0, 0, 0], size, 0, 0, 1, Filter::Linear)?
+                .blit_image(current.clone(), [0, 0, 0], size, 0, 0,
+                    destination.clone(), [0, 0, 0], size, 0, level, 1, Filter::Nearest)?;
+            previous = current;
+        }
+        Ok(builder)
+    }
+
+    /// Records the upload of every mip level, downsampled on the CPU.
+    fn upload_mipmaps(
+        mut builder: AutoCommandBufferBuilder,
+        graphics_queue: &Arc<Queue>,
+        texture: &Texture,
+        color_space: TextureColorSpace,
+        destination: &Arc<impl ImageAccess + Send + Sync + 'static>,
+    ) -> Result<AutoCommandBufferBuilder, RendererError> {
+        let mut level_texture = texture.clone();
+        for level in 0..texture.mip_levels() {
+            if level > 0 {
+                level_texture = level_texture.downsample(color_space);
+            }
+            let pixels = CpuAccessibleBuffer::from_iter(graphics_queue.device().clone(), BufferUsage::transfer_source(),
+                false, level_texture.pixels.iter().cloned())?;
+            builder = builder.copy_buffer_to_image_dimensions(pixels, destination.clone(),
+                [0, 0, 0], [level_texture.width, level_texture.height, 1], 0, 1, level)?;
+        }
+        Ok(builder)
+    }
+
+    fn create_sampler(device: &Arc<Device>, settings: &SamplerSettings) -> Result<Arc<Sampler>, RendererError> {
+        let address_mode = settings.address_mode.address_mode();
+        Ok(Sampler::new(device.clone(), settings.mag_filter.filter(), settings.min_filter.filter(),
+            settings.mipmap_filter.mipmap_mode(), address_mode, address_mode, address_mode,
+            settings.lod_bias, 1.0, settings.min_lod, settings.max_lod)?)
+    }
+
+    /// Replaces all meshes with this one, drawn with the current transforms and texture of the first draw. Handles of the
+    /// previous meshes become invalid.
+    pub fn set_mesh<V: LayoutVertex>(&mut self, mesh: &Mesh<V>) -> Result<(), RendererError> {
+        if let Some(index) = mesh.out_of_range_index() {
+            return Err(RendererError::IndexOutOfRange { index, vertex_count: mesh.vertices.len() });
+        }
+
+        let (transforms, texture) = self.draw_list.draws.first()
+            .map(|draw| (draw.transforms, draw.texture))
+            .unwrap_or_default();
+        self.meshes.clear();
+        self.graphics_pipelines.retain(|&layout, _| layout == V::LAYOUT);
+        self.runtime_shaders.retain(|&layout, _| layout == V::LAYOUT);
+        let mesh = self.add_mesh(mesh)?;
+        self.draw_list = DrawList::single(mesh, transforms);
+        self.draw_list.draws[0].texture = texture;
+        self.prebuild_command_buffers()
+    }
+
+    /// Replaces what is drawn, frames are recorded from it until `prebuild_command_buffers` is called.
+    pub fn set_draw_list(&mut self, draw_list: DrawList) -> Result<(), RendererError> {
+        if let Some(draw) = draw_list.draws.iter().find(|draw| draw.mesh.0 >= self.meshes.len()) {
+            return Err(RendererError::UnknownMesh(draw.mesh));
+        }
+        let unknown_texture = draw_list.draws.iter()
+            .filter_map(|draw| draw.texture)
+            .find(|texture| texture.0 >= self.textures.len());
+        if let Some(texture) = unknown_texture {
+            return Err(RendererError::UnknownTexture(texture));
+        }
+
+        self.draw_list = draw_list;
+        self.default_camera = false;
+        self.command_buffers = None;
+        Ok(())
+    }
+
+    pub fn draw_list(&self) -> &DrawList {
+        &self.draw_list
+    }
+
+    /// Gives every draw these model, view and projection matrices, taking effect from the next frame.
+    pub fn set_transforms(&mut self, transforms: Transforms) {
+        self.draw_list.set_transforms(transforms);
+        self.default_camera = false;
+        self.command_buffers = None;
+    }
+
+    /// Records one command buffer per framebuffer for the current draw list and replays them until it changes,
+    /// instead of recording every frame. Worth it for static scenes.
+    pub fn prebuild_command_buffers(&mut self) -> Result<(), RendererError> {
+        let command_buffers = self.framebuffers.iter()
+            .map(|framebuffer| self.record_command_buffer(framebuffer, true))
+            .collect::<Result<_, RendererError>>()?;
+        self.command_buffers = Some(command_buffers);
+        Ok(())
+    }
+
+    /// The prebuilt command buffer for `framebuffer_index` if the scene is static, a new one otherwise.
+    fn frame_command_buffer(&self, framebuffer_index: usize) -> Result<Arc<AutoCommandBuffer>, RendererError> {
+        match &self.command_buffers {
+            Some(command_buffers) => Ok(command_buffers[framebuffer_index].clone()),
+            None => self.record_command_buffer(&self.framebuffers[framebuffer_index], false),
+        }
+    }
+
+    fn record_command_buffer(
+        &self,
+        framebuffer: &Arc<dyn FramebufferAbstract + Send + Sync>,
+        reusable: bool,
+    ) -> Result<Arc<AutoCommandBuffer>, RendererError> {
+        let queue_family = self.graphics_queue.family();
+        let builder = if reusable {
+            AutoCommandBufferBuilder::primary_simultaneous_use(self.device.clone(), queue_family)?
+        } else {
+            AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), queue_family)?
+        };
+        let mut builder = builder.begin_render_pass(framebuffer.clone(), false, self.clear_values())?;
+        for draw in &self.draw_list.draws {
+            builder = self.draw_mesh(builder, draw)?;
+        }
+        Ok(Arc::new(builder.end_render_pass()?.build()?))
+    }
+
+    fn draw_mesh(&self, builder: AutoCommandBufferBuilder, draw: &Draw) -> Result<AutoCommandBufferBuilder, RendererError> {
+        let mesh = &self.meshes[draw.mesh.0];
+        let pipeline = self.graphics_pipelines[&mesh.layout].clone();
+        let vertex_buffers = vec![mesh.vertex_buffer.clone()];
+        let descriptor_set = self.create_descriptor_set(&pipeline, mesh.layout, draw)?;
+        let builder = match &mesh.index_buffer {
+            Some(IndexBuffer::U16(indices)) => builder.draw_indexed(pipeline,
+                &DynamicState::none(), vertex_buffers, indices.clone(), descriptor_set, ())?,
+            Some(IndexBuffer::U32(indices)) => builder.draw_indexed(pipeline,
+                &DynamicState::none(), vertex_buffers, indices.clone(), descriptor_set, ())?,
+            None => builder.draw(pipeline,
+                &DynamicState::none(), vertex_buffers, descriptor_set, ())?,
+        };
+        Ok(builder)
+    }
+
+    /// Uploads the draw's transforms into a new uniform buffer, bound at set 0 of every vertex shader, followed by
+    /// its texture for the textured layouts.
+    fn create_descriptor_set(
+        &self,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        vertex_layout: VertexLayout,
+        draw: &Draw,
+    ) -> Result<Arc<dyn DescriptorSet + Send + Sync>, RendererError> {
+        let uniform_buffer = self.uniform_buffers.next(draw.transforms)?;
+        //Runtime shaders were checked to declare exactly these bindings when the pipeline was built
+        let layout = pipeline.descriptor_set_layout(0).unwrap().clone();
+        let builder = PersistentDescriptorSet::start(layout)
+            .add_buffer(uniform_buffer)?;
+        if !vertex_layout.is_textured() {
+            return Ok(Arc::new(builder.build()?));
+        }
+
+        let texture = match draw.texture {
+            Some(texture) => self.textures[texture.0].clone(),
+            None => self.white_texture.clone(),
+        };
+        Ok(Arc::new(builder
+            .add_sampled_image(texture, self.sampler.clone())?
+            .build()?))
+    }
+
+    /// Width over height of the image frames are rendered into, for projection matrices.
+    pub fn aspect_ratio(&self) -> f32 {
+        let (_, [width, height]) = self.color_target();
+        width as f32 / height as f32
+    }
+
+    fn create_sync_objects(device: &Arc<Device>) -> Box<dyn GpuFuture> {
+        Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>
+    }
+
+    fn find_queue_families(surface: Option<&Arc<Surface<Window>>>, device: &PhysicalDevice) -> QueueFamilyIndices {
+        let mut indices = QueueFamilyIndices::new();
+        //TODO: replace index with id to simplify?
+        for (i, queue_family) in device.queue_families().enumerate() {
+            if queue_family.supports_graphics() {
+                indices.graphics_family = i as i32;
+            }
+
+            let can_present = match surface {
+                Some(surface) => surface.is_supported(queue_family).unwrap_or(false),
+                None => queue_family.supports_graphics(), //Headless: the graphics queue stands in
+            };
+            if can_present {
+                indices.present_family = i as i32;
+            }
+
+            if indices.is_complete() {
+                break;
+            }
+        }
+
+        indices
+    }
+
+    fn create_logical_device(
+        instance: &Arc<Instance>,
+        surface: Option<&Arc<Surface<Window>>>,
+        physical_device_index: usize,
+        required_features: &Features,
+    ) -> Result<DeviceWithQueues, RendererError> {
+        let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
+        let indices = Self::find_queue_families(surface, &physical_device);
+
+        let families = [indices.graphics_family, indices.present_family];
+        use std::iter::FromIterator;
+        let unique_queue_families: HashSet<&i32> = HashSet::from_iter(families.iter());
+
+        let queue_priority = 1.0;
+        let queue_families = unique_queue_families.iter().map(|i| {
+            (physical_device.queue_families().nth(**i as usize).unwrap(), queue_priority)
+        });
+
+        let (device, mut queues) = Device::new(physical_device, required_features, 
+        &device_extensions(surface.is_some()), queue_families)?;
+
+        let graphics_queue = queues.next().unwrap();
+        let present_queue = queues.next().unwrap_or_else(|| graphics_queue.clone());
+
+        Ok((device, graphics_queue, present_queue))
+    }
+
+    pub fn draw_frame(&mut self) -> Result<(), RendererError> {
+        self.render_frame(false)?;
+        self.check_validation()
+    }
+
+    /// Renders a frame and reads the presented image back as RGBA8.
+    ///
+    /// Returns `None` if the swapchain was out of date and no frame could be rendered.
+    pub fn capture_frame(&mut self) -> Result<Option<FrameCapture>, RendererError> {
+        let frame = self.render_frame(true)?;
+        self.check_validation()?;
+        Ok(frame)
+    }
+
+    /// Fails with the validation errors reported since the last check when strict validation is enabled.
+    fn check_validation(&self) -> Result<(), RendererError> {
+        let errors = self.validation_log.take_new_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RendererError::Validation(errors))
+        }
+    }
+
+    pub fn save_screenshot<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RendererError> {
+        let capture = self.capture_frame()?.ok_or(RendererError::FrameSkipped)?;
+        Ok(capture.save_png(path)?)
+    }
+
+    fn render_frame(&mut self, capture: bool) -> Result<Option<FrameCapture>, RendererError> {
+        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+        if self.swap_chain.is_none() {
+            return self.draw_offscreen_frame(capture);
+        }
+
+        if self.recreate_swapchain {
+            self.recreate_swap_chain()?;
+            self.recreate_swapchain = false;
+        }
+
+        let swap_chain = self.swap_chain.clone().unwrap();
+        let (image_index, suboptimal, acquire_future) = match acquire_next_image(swap_chain.clone(), None) {
+            Ok(r) => r,
+            Err(AcquireError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                return Ok(None);
+            },
+            Err(e) => return Err(e.into())
+        };
+
+        let command_buffer = self.frame_command_buffer(image_index)?;
+        let readback_buffer = if capture { Some(self.create_readback_buffer()?) } else { None };
+
+        let future = self.previous_frame_end.take().unwrap()
+            .join(acquire_future)
+            .then_execute(self.graphics_queue.clone(), command_buffer)?;
+        let future: Box<dyn GpuFuture> = match &readback_buffer {
+            Some(buffer) => {
+                let image = self.swap_chain_images[image_index].clone();
+                Box::new(future.then_execute(self.graphics_queue.clone(), self.create_readback_command_buffer(image, buffer)?)?)
+            }
+            None => Box::new(future),
+        };
+        let future = future
+            .then_swapchain_present(self.graphics_queue.clone(), swap_chain, image_index)
+            .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                if readback_buffer.is_some() {
+                    future.wait(None)?;
+                }
+                self.previous_frame_end = Some(Box::new(future) as Box<_>);
+            }
+            Err(vulkano::sync::FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())) as Box<_>);
+                return Ok(None);
+            }
+            Err(e) => {
+                self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())) as Box<_>);
+                return Err(e.into());
+            }
+        }
+
+        readback_buffer.map(|buffer| self.read_back(&buffer)).transpose()
+    }
+
+    /// Renders a single frame into the offscreen image and waits for it to complete.
+    fn draw_offscreen_frame(&mut self, capture: bool) -> Result<Option<FrameCapture>, RendererError> {
+        let command_buffer = self.frame_command_buffer(0)?;
+        let readback_buffer = if capture { Some(self.create_readback_buffer()?) } else { None };
+
+        let future = self.previous_frame_end.take().unwrap()
+            .then_execute(self.graphics_queue.clone(), command_buffer)?;
+        let future: Box<dyn GpuFuture> = match &readback_buffer {
+            Some(buffer) => {
+                let image = self.offscreen_image.clone().unwrap();
+                Box::new(future.then_execute(self.graphics_queue.clone(), self.create_readback_command_buffer(image, buffer)?)?)
+            }
+            None => Box::new(future),
+        };
+        let future = match future.then_signal_fence_and_flush() {
+            Ok(future) => future,
+            Err(e) => {
+                self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())) as Box<_>);
+                return Err(e.into());
+            }
+        };
+        future.wait(None)?;
+
+        self.previous_frame_end = Some(Box::new(future) as Box<_>);
+        readback_buffer.map(|buffer| self.read_back(&buffer)).transpose()
+    }
+
+    /// Format and dimensions of the image frames are rendered into.
+    fn color_target(&self) -> (Format, [u32; 2]) {
+        match &self.swap_chain {
+            Some(swap_chain) => (swap_chain.format(), swap_chain.dimensions()),
+            None => (OFFSCREEN_FORMAT, [self.config.width, self.config.height]),
+        }
+    }
+
+    fn create_readback_buffer(&self) -> Result<Arc<CpuAccessibleBuffer<[u8]>>, RendererError> {
+        let (format, [width, height]) = self.color_target();
+        let size = (width * height) as usize * format.size()
+            .ok_or(RendererError::UnsupportedReadbackFormat(format))?;
+        let usage = BufferUsage {
+            transfer_destination: true,
+            .. BufferUsage::none()
+        };
+        Ok(CpuAccessibleBuffer::from_iter(self.device.clone(), usage, true, (0..size).map(|_| 0u8))?)
+    }
+
+    fn create_readback_command_buffer<I>(
+        &self,
+        image: I,
+        buffer: &Arc<CpuAccessibleBuffer<[u8]>>,
+    ) -> Result<AutoCommandBuffer, RendererError>
+        where I: ImageAccess + Send + Sync + 'static
+    {
+        Ok(AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), self.graphics_queue.family())?
+            .copy_image_to_buffer(image, buffer.clone())?
+            .build()?)
+    }
+
+    fn read_back(&self, buffer: &Arc<CpuAccessibleBuffer<[u8]>>) -> Result<FrameCapture, RendererError> {
+        let (format, [width, height]) = self.color_target();
+        let data = buffer.read()?;
+        FrameCapture::from_raw(width, height, format, &data)
+    }
+
+    /// Rebuilds the pipelines when a file in `RendererConfig::shader_dir` changed, meant to be called every frame. If a
+    /// shader fails to compile the error is printed and the previous pipelines are kept.
+    ///
+    /// Returns whether the pipelines were replaced.
+    pub fn reload_changed_shaders(&mut self) -> Result<bool, RendererError> {
+        let changed = match &mut self.shader_watcher {
+            Some(watcher) => watcher.poll(),
+            None => false,
+        };
+        if !changed {
+            return Ok(false);
+        }
+
+        //The only place shaders are read again, everything else rebuilds from the cached ones so that a broken file
+        //on disk cannot fail a resize
+        let (_, extent) = self.color_target();
+        let shader_dir = match &self.config.shader_dir {
+            Some(dir) => dir,
+            None => return Ok(false),
+        };
+        let reloaded = self.graphics_pipelines.keys()
+            .map(|&layout| {
+                let shaders = Self::load_runtime_shaders(&self.device, shader_dir, layout)?;
+                let pipeline = Self::create_graphics_pipeline(&self.device, extent, &self.render_pass, layout,
+                    Some(&shaders))?;
+                Ok(((layout, shaders), (layout, pipeline)))
+            })
+            .collect::<Result<Vec<_>, RendererError>>();
+        match reloaded {
+            Ok(reloaded) => {
+                log::info!("Shaders reloaded");
+                let (shaders, pipelines): (HashMap<_, _>, HashMap<_, _>) = reloaded.into_iter().unzip();
+                self.runtime_shaders = shaders;
+                self.graphics_pipelines = pipelines;
+                if self.command_buffers.is_some() {
+                    self.prebuild_command_buffers()?;
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                log::error!("Keeping the previous shaders: {}", e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// One per render pass attachment, the resolved color target is not cleared.
+    fn clear_values(&self) -> Vec<ClearValue> {
+        let clear_color = self.config.clear_color.into();
+        if self.msaa_color_image.is_some() {
+            vec![clear_color, ClearValue::None, 1f32.into()]
+        } else {
+            vec![clear_color, 1f32.into()]
+        }
+    }
+
+    fn recreate_swap_chain(&mut self) -> Result<(), RendererError> {
+        let surface = self.surface.as_ref().unwrap();
+        let (swap_chain, images) = Self::create_swap_chain(&self.config, self.physical_device(), surface, &self.device,
+            &self.graphics_queue, &self.present_queue, self.swap_chain.clone())?;
+        self.swap_chain_images = images;
+
+        self.depth_image = Self::create_depth_image(&self.device, swap_chain.dimensions(), self.depth_format, self.samples)?;
+        self.msaa_color_image = Self::create_msaa_color_image(&self.device, swap_chain.dimensions(), swap_chain.format(),
+            self.samples)?;
+        self.render_pass = Self::create_render_pass(&self.device, swap_chain.format(), self.depth_format, self.samples)?;
+        for (&layout, pipeline) in self.graphics_pipelines.iter_mut() {
+            *pipeline = Self::create_graphics_pipeline(&self.device, swap_chain.dimensions(), &self.render_pass, layout,
+                self.runtime_shaders.get(&layout))?;
+        }
+        self.framebuffers = Self::create_framebuffers(&self.swap_chain_images, &self.depth_image,
+            self.msaa_color_image.as_ref(), &self.render_pass)?;
+        self.swap_chain = Some(swap_chain);
+        if self.default_camera {
+            self.draw_list.set_transforms(Transforms::camera(self.aspect_ratio()));
+        }
+        if self.command_buffers.is_some() {
+            self.prebuild_command_buffers()?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the swapchain before the next frame, e.g. after the window was resized.
+    pub fn request_swapchain_recreation(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    pub fn config(&self) -> &RendererConfig {
+        &self.config
+    }
+
+    pub fn instance(&self) -> &Arc<Instance> {
+        &self.instance
+    }
+
+    pub fn physical_device(&self) -> PhysicalDevice<'_> {
+        PhysicalDevice::from_index(&self.instance, self.physical_device_index).unwrap()
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn graphics_queue(&self) -> &Arc<Queue> {
+        &self.graphics_queue
+    }
+
+    /// `None` when rendering headless.
+    pub fn surface(&self) -> Option<&Arc<Surface<Window>>> {
+        self.surface.as_ref()
+    }
+
+    pub fn queue_family_indices(&self) -> QueueFamilyIndices {
+        Self::find_queue_families(self.surface.as_ref(), &self.physical_device())
+    }
+
+    /// Names of the instance layers that were actually enabled.
+    pub fn enabled_layers(&self) -> Vec<String> {
+        self.instance.loaded_layers()
+            .map(|layer| layer.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Messages captured by the debug callback, empty unless strict validation is enabled.
+    pub fn validation_log(&self) -> &ValidationLog {
+        &self.validation_log
+    }
+}
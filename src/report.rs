@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use vulkano::device::{DeviceExtensions, RawDeviceExtensions};
+use vulkano::image::ImageUsage;
+use vulkano::instance::{InstanceExtensions, LayerProperties, PhysicalDevice, RawInstanceExtensions};
+use vulkano::swapchain::{Capabilities, Surface};
+use winit::window::Window;
+
+use crate::gpu::GpuInfo;
+
+/// Everything the renderer can query about the Vulkan installation, written by `--capability-report`.
+///
+/// Lists are sorted so reports from different machines or drivers can be diffed.
+#[derive(Clone, Debug, Serialize)]
+pub struct CapabilityReport {
+    pub instance_extensions: Vec<String>,
+    pub layers: Vec<LayerReport>,
+    pub devices: Vec<DeviceReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LayerReport {
+    pub name: String,
+    pub description: String,
+    pub vulkan_version: String,
+    pub implementation_version: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceReport {
+    #[serde(flatten)]
+    pub info: GpuInfo,
+    pub extensions: Vec<String>,
+    /// `None` when headless or when the capabilities could not be queried
+    pub surface: Option<SurfaceReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SurfaceReport {
+    pub min_image_count: u32,
+    pub max_image_count: Option<u32>,
+    pub current_extent: Option<[u32; 2]>,
+    pub min_image_extent: [u32; 2],
+    pub max_image_extent: [u32; 2],
+    pub max_image_array_layers: u32,
+    pub formats: Vec<SurfaceFormatReport>,
+    pub present_modes: Vec<String>,
+    pub composite_alpha: Vec<String>,
+    pub transforms: Vec<String>,
+    pub usage: Vec<&'static str>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SurfaceFormatReport {
+    pub format: String,
+    pub color_space: String,
+}
+
+impl CapabilityReport {
+    pub fn new(
+        instance_extensions: &InstanceExtensions,
+        layers: impl IntoIterator<Item = LayerProperties>,
+        devices: Vec<DeviceReport>,
+    ) -> Self {
+        let mut instance_extensions: Vec<_> = RawInstanceExtensions::from(instance_extensions).iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        instance_extensions.sort();
+
+        let mut layers: Vec<_> = layers.into_iter()
+            .map(|layer| LayerReport {
+                name: layer.name().to_owned(),
+                description: layer.description().to_owned(),
+                vulkan_version: layer.vulkan_version().to_string(),
+                implementation_version: layer.implementation_version(),
+            })
+            .collect();
+        layers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self { instance_extensions, layers, devices }
+    }
+
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), serde_json::Error> {
+        let file = File::create(path).map_err(serde_json::Error::io)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+    }
+}
+
+impl DeviceReport {
+    pub fn new(device: &PhysicalDevice, surface: Option<&Arc<Surface<Window>>>, info: GpuInfo) -> Self {
+        let mut extensions: Vec<_> = RawDeviceExtensions::from(&DeviceExtensions::supported_by_device(*device)).iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        extensions.sort();
+
+        let surface = surface
+            .and_then(|surface| surface.capabilities(*device).ok())
+            .map(|capabilities| SurfaceReport::new(&capabilities));
+
+        Self { info, extensions, surface }
+    }
+}
+
+impl SurfaceReport {
+    fn new(capabilities: &Capabilities) -> Self {
+        Self {
+            min_image_count: capabilities.min_image_count,
+            max_image_count: capabilities.max_image_count,
+            current_extent: capabilities.current_extent,
+            min_image_extent: capabilities.min_image_extent,
+            max_image_extent: capabilities.max_image_extent,
+            max_image_array_layers: capabilities.max_image_array_layers,
+            formats: capabilities.supported_formats.iter()
+                .map(|(format, color_space)| SurfaceFormatReport {
+                    format: format!("{:?}", format),
+                    color_space: format!("{:?}", color_space),
+                })
+                .collect(),
+            present_modes: capabilities.present_modes.iter().map(|mode| format!("{:?}", mode)).collect(),
+            composite_alpha: capabilities.supported_composite_alpha.iter()
+                .map(|alpha| format!("{:?}", alpha))
+                .collect(),
+            transforms: capabilities.supported_transforms.iter()
+                .map(|transform| format!("{:?}", transform))
+                .collect(),
+            usage: usage_names(&capabilities.supported_usage_flags),
+        }
+    }
+}
+
+fn usage_names(usage: &ImageUsage) -> Vec<&'static str> {
+    let flags = [
+        (usage.transfer_source, "transfer_source"),
+        (usage.transfer_destination, "transfer_destination"),
+        (usage.sampled, "sampled"),
+        (usage.storage, "storage"),
+        (usage.color_attachment, "color_attachment"),
+        (usage.depth_stencil_attachment, "depth_stencil_attachment"),
+        (usage.transient_attachment, "transient_attachment"),
+        (usage.input_attachment, "input_attachment"),
+    ];
+    flags.iter().filter(|(set, _)| *set).map(|&(_, name)| name).collect()
+}
@@ -0,0 +1,527 @@
+use std::error;
+use std::fmt;
+use std::path::Path;
+
+use gltf::mesh::Mode;
+
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+use crate::vertex::MeshVertex;
+
+/// Extensions whose absence does not change how the scene looks
+const SUPPORTED_EXTENSIONS: &[&str] = &[];
+
+/// Column-major 4x4 matrix, as stored in glTF.
+pub type Matrix4 = [[f32; 4]; 4];
+
+const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[derive(Debug)]
+pub enum SceneError {
+    Gltf(gltf::Error),
+    /// An attribute accessor has a different number of elements than the primitive's positions
+    AttributeCount { mesh: usize, attribute: &'static str, count: usize, positions: usize },
+    /// An index points past the end of the primitive's positions
+    IndexOutOfRange { mesh: usize, index: u32, positions: usize },
+    /// A node is reached twice from the scene roots, the node hierarchy has a cycle or a shared child
+    NodeHierarchy { node: usize },
+}
+
+impl error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SceneError::Gltf(e) => Some(e),
+            SceneError::AttributeCount { .. } => None,
+            SceneError::IndexOutOfRange { .. } => None,
+            SceneError::NodeHierarchy { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Gltf(e) => write!(fmt, "{}", e),
+            SceneError::AttributeCount { mesh, attribute, count, positions } =>
+                write!(fmt, "mesh {}: {} has {} elements but there are {} positions", mesh, attribute, count, positions),
+            SceneError::IndexOutOfRange { mesh, index, positions } =>
+                write!(fmt, "mesh {}: index {} is out of range for {} positions", mesh, index, positions),
+            SceneError::NodeHierarchy { node } =>
+                write!(fmt, "node {} is reached more than once, the node hierarchy is not a tree", node),
+        }
+    }
+}
+
+impl From<gltf::Error> for SceneError {
+    fn from(err: gltf::Error) -> Self {
+        SceneError::Gltf(err)
+    }
+}
+
+/// Triangle list sharing one material. Optional attributes are `None` when the file does not provide them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Primitive {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    /// `None` for non-indexed primitives
+    pub indices: Option<Vec<u32>>,
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    /// Linear RGBA multiplied with the vertex colors and the base color texture
+    pub base_color: [f32; 4],
+    /// Index into `Scene::textures`
+    pub base_color_texture: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Relative to the parent node
+    pub transform: Matrix4,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// Contents of a glTF or GLB file, the indices in each part refer to the other vectors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub meshes: Vec<SceneMesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<Node>,
+    /// Top-level nodes of the default scene
+    pub roots: Vec<usize>,
+    /// Parts of the file that were ignored, also logged when loading
+    pub warnings: Vec<String>,
+}
+
+impl Scene {
+    /// Loads a `.gltf` file with embedded or external buffers and images, or a binary `.glb` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let (document, buffers, images) = gltf::import(path)?;
+        Self::from_document(&document, &buffers, &images)
+    }
+
+    /// Loads GLB contents from memory, buffers and images referenced by URI cannot be resolved.
+    pub fn from_slice(slice: &[u8]) -> Result<Self, SceneError> {
+        let (document, buffers, images) = gltf::import_slice(slice)?;
+        Self::from_document(&document, &buffers, &images)
+    }
+
+    fn from_document(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<Self, SceneError> {
+        let mut warnings = vec![];
+        for extension in document.extensions_used() {
+            if !SUPPORTED_EXTENSIONS.contains(&extension) {
+                warnings.push(format!("extension {} is not supported and was ignored", extension));
+            }
+        }
+
+        let mut meshes = vec![];
+        for mesh in document.meshes() {
+            let mut primitives = vec![];
+            for primitive in mesh.primitives() {
+                match read_primitive(mesh.index(), &primitive, buffers)? {
+                    Some(primitive) => primitives.push(primitive),
+                    None => warnings.push(format!(
+                        "mesh {}: skipped a primitive that is not a triangle list with positions", mesh.index())),
+                }
+            }
+            meshes.push(SceneMesh { name: mesh.name().map(str::to_owned), primitives });
+        }
+
+        let materials = document.materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                Material {
+                    name: material.name().map(str::to_owned),
+                    base_color: pbr.base_color_factor(),
+                    base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+                }
+            })
+            .collect();
+
+        let textures = document.textures()
+            .map(|texture| {
+                let image = &images[texture.source().index()];
+                to_rgba8(image).unwrap_or_else(|| {
+                    warnings.push(format!("texture {}: {:?} images are not supported, using white",
+                        texture.index(), image.format));
+                    Texture::solid([255; 4])
+                })
+            })
+            .collect();
+
+        let nodes = document.nodes()
+            .map(|node| Node {
+                name: node.name().map(str::to_owned),
+                transform: node.transform().matrix(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect();
+
+        let roots = document.default_scene()
+            .or_else(|| document.scenes().next())
+            .map(|scene| scene.nodes().map(|node| node.index()).collect())
+            .unwrap_or_default();
+
+        let mut scene = Self { meshes, materials, textures, nodes, roots, warnings };
+        let (_, revisited) = scene.walk();
+        if let Some(&node) = revisited.first() {
+            return Err(SceneError::NodeHierarchy { node });
+        }
+
+        let textures = scene.drawn_base_color_textures();
+        if textures.len() > 1 {
+            scene.warnings.push(format!("the scene uses {} base color textures, only texture {} is drawn",
+                textures.len(), textures[0]));
+        }
+
+        for warning in &scene.warnings {
+            log::warn!("glTF: {}", warning);
+        }
+        Ok(scene)
+    }
+
+    /// Nodes reachable from the roots with their parent, parents before children. The second column lists nodes
+    /// reached again, they are not descended into a second time, so cycles in hand-built scenes cannot loop forever.
+    fn walk(&self) -> (Vec<(usize, Option<usize>)>, Vec<usize>) {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut order = vec![];
+        let mut revisited = vec![];
+        let mut stack: Vec<_> = self.roots.iter().rev().map(|&root| (root, None)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            if visited[index] {
+                revisited.push(index);
+                continue;
+            }
+            visited[index] = true;
+            order.push((index, parent));
+            stack.extend(self.nodes[index].children.iter().rev().map(|&child| (child, Some(index))));
+        }
+        (order, revisited)
+    }
+
+    /// Transform of every node relative to the scene, identity for nodes outside of it.
+    pub fn world_transforms(&self) -> Vec<Matrix4> {
+        let mut transforms = vec![IDENTITY; self.nodes.len()];
+        for (index, parent) in self.walk().0 {
+            let parent = parent.map(|parent| transforms[parent]).unwrap_or(IDENTITY);
+            transforms[index] = multiply(&parent, &self.nodes[index].transform);
+        }
+        transforms
+    }
+
+    /// Indices into `textures` used as base color by the drawn primitives, in drawing order.
+    fn drawn_base_color_textures(&self) -> Vec<usize> {
+        let mut textures = vec![];
+        for (index, _) in self.walk().0 {
+            let primitives = self.nodes[index].mesh.iter().flat_map(|&mesh| &self.meshes[mesh].primitives);
+            for primitive in primitives {
+                let texture = primitive.material.and_then(|material| self.materials[material].base_color_texture);
+                if let Some(texture) = texture {
+                    if !textures.contains(&texture) {
+                        textures.push(texture);
+                    }
+                }
+            }
+        }
+        textures
+    }
+
+    /// Base color texture of the first drawn primitive that has one. `to_mesh` merges every primitive into one
+    /// mesh, so a single texture is drawn for all of them.
+    pub fn base_color_texture(&self) -> Option<&Texture> {
+        self.drawn_base_color_textures().first().map(|&texture| &self.textures[texture])
+    }
+
+    /// Every primitive placed by its node transform and merged into one mesh, colors combine the vertex colors with
+    /// the material's base color.
+    pub fn to_mesh(&self) -> Mesh<MeshVertex> {
+        let transforms = self.world_transforms();
+        let mut vertices = vec![];
+        let mut indices = vec![];
+
+        for (index, _) in self.walk().0 {
+            let node = &self.nodes[index];
+            let mesh = match node.mesh {
+                Some(mesh) => &self.meshes[mesh],
+                None => continue,
+            };
+            for primitive in &mesh.primitives {
+                let base_color = primitive.material
+                    .map(|material| self.materials[material].base_color)
+                    .unwrap_or([1.0; 4]);
+                let offset = vertices.len() as u32;
+
+                let transform = &transforms[index];
+                vertices.extend(primitive.positions.iter().enumerate().map(|(i, &position)| {
+                    let normal = primitive.normals.as_ref()
+                        .map(|normals| normalize(transform_direction(transform, normals[i])))
+                        .unwrap_or_default();
+                    let uv = primitive.uvs.as_ref().map(|uvs| uvs[i]).unwrap_or_default();
+                    let tangent = primitive.tangents.as_ref()
+                        .map(|tangents| {
+                            let [x, y, z, w] = tangents[i];
+                            let [x, y, z] = normalize(transform_direction(transform, [x, y, z]));
+                            [x, y, z, w]
+                        })
+                        .unwrap_or_default();
+                    let vertex_color = primitive.colors.as_ref().map(|colors| colors[i]).unwrap_or([1.0; 4]);
+                    let color = [
+                        base_color[0] * vertex_color[0],
+                        base_color[1] * vertex_color[1],
+                        base_color[2] * vertex_color[2],
+                    ];
+                    MeshVertex {
+                        position: transform_point(transform, position),
+                        normal,
+                        uv,
+                        tangent,
+                        color,
+                    }
+                }));
+                match &primitive.indices {
+                    Some(primitive_indices) => indices.extend(primitive_indices.iter().map(|index| index + offset)),
+                    None => indices.extend(offset..offset + primitive.positions.len() as u32),
+                }
+            }
+        }
+
+        Mesh::indexed(vertices, indices)
+    }
+}
+
+/// `None` for primitives that are not triangle lists or have no positions, an error when the attributes and indices
+/// do not fit the positions.
+fn read_primitive(
+    mesh: usize,
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Option<Primitive>, SceneError> {
+    if primitive.mode() != Mode::Triangles {
+        return Ok(None);
+    }
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<_> = match reader.read_positions() {
+        Some(positions) => positions.collect(),
+        None => return Ok(None),
+    };
+    let primitive = Primitive {
+        normals: reader.read_normals().map(|normals| normals.collect()),
+        tangents: reader.read_tangents().map(|tangents| tangents.collect()),
+        uvs: reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect()),
+        colors: reader.read_colors(0).map(|colors| colors.into_rgba_f32().collect()),
+        indices: reader.read_indices().map(|indices| indices.into_u32().collect()),
+        material: primitive.material().index(),
+        positions,
+    };
+    check_primitive(mesh, &primitive)?;
+    Ok(Some(primitive))
+}
+
+fn check_primitive(mesh: usize, primitive: &Primitive) -> Result<(), SceneError> {
+    let positions = primitive.positions.len();
+    let counts = [
+        ("NORMAL", primitive.normals.as_ref().map(Vec::len)),
+        ("TANGENT", primitive.tangents.as_ref().map(Vec::len)),
+        ("TEXCOORD_0", primitive.uvs.as_ref().map(Vec::len)),
+        ("COLOR_0", primitive.colors.as_ref().map(Vec::len)),
+    ];
+    for &(attribute, count) in &counts {
+        match count {
+            Some(count) if count != positions =>
+                return Err(SceneError::AttributeCount { mesh, attribute, count, positions }),
+            _ => {}
+        }
+    }
+    let out_of_range = primitive.indices.iter().flatten().find(|&&index| index as usize >= positions);
+    match out_of_range {
+        Some(&index) => Err(SceneError::IndexOutOfRange { mesh, index, positions }),
+        None => Ok(()),
+    }
+}
+
+/// `None` for 16-bit formats.
+fn to_rgba8(image: &gltf::image::Data) -> Option<Texture> {
+    use gltf::image::Format;
+
+    let pixels = match image.format {
+        Format::R8 => image.pixels.iter().flat_map(|&r| vec![r, r, r, 255]).collect(),
+        Format::R8G8 => image.pixels.chunks_exact(2).flat_map(|p| vec![p[0], p[1], 0, 255]).collect(),
+        Format::R8G8B8 => image.pixels.chunks_exact(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+        Format::R8G8B8A8 => image.pixels.clone(),
+        Format::B8G8R8 => image.pixels.chunks_exact(3).flat_map(|p| vec![p[2], p[1], p[0], 255]).collect(),
+        Format::B8G8R8A8 => image.pixels.chunks_exact(4).flat_map(|p| vec![p[2], p[1], p[0], p[3]]).collect(),
+        _ => return None,
+    };
+    Some(Texture { width: image.width, height: image.height, pixels })
+}
+
+fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut result = [[0.0; 4]; 4];
+    for (column, result_column) in result.iter_mut().enumerate() {
+        for (row, value) in result_column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[column][k]).sum();
+        }
+    }
+    result
+}
+
+fn transform_point(matrix: &Matrix4, point: [f32; 3]) -> [f32; 3] {
+    let mut result = [0.0; 3];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = matrix[0][row] * point[0] + matrix[1][row] * point[1] + matrix[2][row] * point[2] + matrix[3][row];
+    }
+    result
+}
+
+/// Applies the upper 3x3 of `matrix`, ignoring translation.
+fn transform_direction(matrix: &Matrix4, direction: [f32; 3]) -> [f32; 3] {
+    let mut result = [0.0; 3];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = matrix[0][row] * direction[0] + matrix[1][row] * direction[1] + matrix[2][row] * direction[2];
+    }
+    result
+}
+
+/// Zero vectors are left as they are.
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = (vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2]).sqrt();
+    if length == 0.0 {
+        return vector;
+    }
+    [vector[0] / length, vector[1] / length, vector[2] / length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_file::TempFile;
+
+    /// One triangle `(0,0) (1,0) (0,1)` drawn by a child node scaled by 2 under a root translated by 1 along X
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["EXT_made_up"],
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "name": "root", "translation": [1, 0, 0], "children": [1] },
+            { "name": "child", "scale": [2, 2, 2], "mesh": 0 }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] } }],
+        "buffers": [{
+            "byteLength": 44,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="
+        }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ]
+    }"#;
+
+    /// Slice imports cannot resolve the data URI, so the scene goes through a file
+    fn load(name: &str, gltf: &str) -> Result<Scene, SceneError> {
+        Scene::load(TempFile::with_contents(name, "gltf", gltf).path())
+    }
+
+    fn load_triangle(name: &str) -> Scene {
+        load(name, TRIANGLE).unwrap()
+    }
+
+    #[test]
+    fn primitives_and_materials_are_read() {
+        let scene = load_triangle("primitives");
+        let primitive = &scene.meshes[0].primitives[0];
+        assert_eq!(primitive.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(primitive.indices, Some(vec![0, 1, 2]));
+        assert_eq!(primitive.normals, None);
+        assert_eq!(scene.materials[primitive.material.unwrap()].base_color, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn unsupported_extensions_are_warnings() {
+        let scene = load_triangle("extensions");
+        assert_eq!(scene.warnings.len(), 1);
+        assert!(scene.warnings[0].contains("EXT_made_up"));
+    }
+
+    #[test]
+    fn node_transforms_are_combined() {
+        let mesh = load_triangle("transforms").to_mesh();
+        let positions: Vec<_> = mesh.vertices.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions, vec![[1.0, 0.0, 0.0], [3.0, 0.0, 0.0], [1.0, 2.0, 0.0]]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.color == [1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn attributes_must_fit_the_positions() {
+        let short_normals = TRIANGLE
+            .replace(r#""POSITION": 0 }"#, r#""POSITION": 0, "NORMAL": 2 }"#)
+            .replace(r#""type": "SCALAR" }"#, r#""type": "SCALAR" },
+            { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" }"#);
+        match load("short-normals", &short_normals) {
+            Err(SceneError::AttributeCount { mesh: 0, attribute: "NORMAL", count: 2, positions: 3 }) => {}
+            other => panic!("expected an attribute count error, got {:?}", other),
+        }
+
+        let two_positions = TRIANGLE.replace(r#""count": 3, "type": "VEC3""#, r#""count": 2, "type": "VEC3""#);
+        match load("two-positions", &two_positions) {
+            Err(SceneError::IndexOutOfRange { mesh: 0, index: 2, positions: 2 }) => {}
+            other => panic!("expected an index error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn node_cycles_are_rejected() {
+        let cycle = TRIANGLE.replace(r#""mesh": 0 }"#, r#""mesh": 0, "children": [0] }"#);
+        match load("cycle", &cycle) {
+            Err(SceneError::NodeHierarchy { node: 0 }) => {}
+            other => panic!("expected a hierarchy error, got {:?}", other),
+        }
+
+        let mut scene = load_triangle("hand-built-cycle");
+        scene.nodes[1].children.push(0);
+        assert_eq!(scene.world_transforms().len(), 2);
+        assert_eq!(scene.to_mesh().vertices.len(), 3);
+    }
+
+    #[test]
+    fn the_first_drawn_base_color_texture_is_used() {
+        let mut scene = load_triangle("base-color-texture");
+        assert_eq!(scene.base_color_texture(), None);
+
+        scene.textures = vec![Texture::solid([255, 0, 0, 255]), Texture::solid([0, 255, 0, 255])];
+        scene.materials[0].base_color_texture = Some(1);
+        assert_eq!(scene.base_color_texture(), Some(&scene.textures[1]));
+    }
+}
@@ -0,0 +1,218 @@
+use std::error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use vulkano::device::Device;
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderModule};
+
+use crate::error::RendererError;
+use crate::reflect::{
+    DescriptorBinding,
+    ReflectError,
+    ReflectedInterface,
+    ReflectedLayout,
+    ShaderReflection,
+    SPIRV_MAGIC,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    fn kind(self) -> shaderc::ShaderKind {
+        match self {
+            ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+        }
+    }
+}
+
+/// Reads a SPIR-V module, in either byte order.
+pub fn load_spirv(path: &Path) -> Result<Vec<u32>, ShaderError> {
+    let bytes = fs::read(path).map_err(|e| ShaderError::Io(path.to_owned(), e))?;
+    if bytes.len() % 4 != 0 {
+        return Err(ShaderError::Reflection(path.to_owned(), ReflectError::Malformed("size is not a multiple of 4")));
+    }
+
+    let words: Vec<u32> = bytes.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    match words.first() {
+        Some(&SPIRV_MAGIC) => Ok(words),
+        Some(&magic) if magic == SPIRV_MAGIC.swap_bytes() => Ok(words.into_iter().map(u32::swap_bytes).collect()),
+        _ => Err(ShaderError::Reflection(path.to_owned(), ReflectError::Malformed("missing SPIR-V header"))),
+    }
+}
+
+/// The precompiled `<file>.spv` next to a GLSL `file` if there is one, the GLSL file otherwise.
+pub fn shader_path(dir: &Path, file: &str) -> PathBuf {
+    let spirv = dir.join(format!("{}.spv", file));
+    if spirv.exists() {
+        spirv
+    } else {
+        dir.join(file)
+    }
+}
+
+/// Loads `.spv` files as they are and compiles anything else as GLSL.
+pub fn load_shader(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    if path.extension() == Some("spv".as_ref()) {
+        load_spirv(path)
+    } else {
+        compile_glsl(path, stage)
+    }
+}
+
+/// Compiles a GLSL file to SPIR-V words, warnings are logged.
+pub fn compile_glsl(path: &Path, stage: ShaderStage) -> Result<Vec<u32>, ShaderError> {
+    let source = fs::read_to_string(path).map_err(|e| ShaderError::Io(path.to_owned(), e))?;
+    let mut compiler = shaderc::Compiler::new().ok_or(ShaderError::CompilerUnavailable)?;
+    let artifact = compiler.compile_into_spirv(&source, stage.kind(), &path.to_string_lossy(), "main", None)
+        .map_err(|e| ShaderError::Compilation(path.to_owned(), e))?;
+    if artifact.get_num_warnings() > 0 {
+        log::warn!("{}: {}", path.display(), artifact.get_warning_messages());
+    }
+    Ok(artifact.as_binary().to_vec())
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(PathBuf, io::Error),
+    /// shaderc could not be initialized
+    CompilerUnavailable,
+    Compilation(PathBuf, shaderc::Error),
+    Reflection(PathBuf, ReflectError),
+}
+
+impl error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ShaderError::Io(_, e) => Some(e),
+            ShaderError::CompilerUnavailable => None,
+            ShaderError::Compilation(_, e) => Some(e),
+            ShaderError::Reflection(_, e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io(path, e) => write!(fmt, "{}: {}", path.display(), e),
+            ShaderError::CompilerUnavailable => write!(fmt, "failed to initialize the shaderc compiler"),
+            ShaderError::Compilation(path, e) => write!(fmt, "{}: {}", path.display(), e),
+            ShaderError::Reflection(path, e) => write!(fmt, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+/// A shader module loaded at runtime, described by the interfaces reflected from its SPIR-V.
+pub(crate) struct RuntimeShader {
+    module: Arc<ShaderModule>,
+    name: CString,
+    reflection: ShaderReflection,
+    input: ReflectedInterface,
+    output: ReflectedInterface,
+    layout: ReflectedLayout,
+}
+
+impl RuntimeShader {
+    pub fn load(device: &Arc<Device>, path: &Path, stage: ShaderStage) -> Result<Self, RendererError> {
+        let words = load_shader(path, stage)?;
+        let reflection = ShaderReflection::new(&words, stage)
+            .map_err(|e| ShaderError::Reflection(path.to_owned(), e))?;
+        //The module is only used through the reflected entry point, which matches its SPIR-V
+        let module = unsafe { ShaderModule::from_words(device.clone(), &words)? };
+        Ok(Self {
+            module,
+            name: CString::new("main").unwrap(),
+            input: reflection.input_interface(),
+            output: reflection.output_interface(),
+            layout: reflection.layout(),
+            reflection,
+        })
+    }
+
+    /// Reflected descriptors, sorted by set and binding.
+    pub fn descriptors(&self) -> &[DescriptorBinding] {
+        &self.reflection.descriptors
+    }
+
+    pub fn entry_point(&self) -> GraphicsEntryPoint<'_, (), ReflectedInterface, ReflectedInterface, ReflectedLayout> {
+        let ty = match self.reflection.stage {
+            ShaderStage::Vertex => GraphicsShaderType::Vertex,
+            ShaderStage::Fragment => GraphicsShaderType::Fragment,
+        };
+        unsafe {
+            self.module.graphics_entry_point(&self.name, self.input.clone(), self.output.clone(), self.layout.clone(), ty)
+        }
+    }
+}
+
+/// Notices changes to shader files by polling their modification time and size.
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    files: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+impl ShaderWatcher {
+    pub fn new<I>(paths: I) -> Self
+        where I: IntoIterator<Item = PathBuf>
+    {
+        let files = paths.into_iter()
+            .map(|path| {
+                let stamp = stamp(&path);
+                (path, stamp)
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Whether any file was modified, created or removed since the last call.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, previous) in &mut self.files {
+            let current = stamp(path);
+            if current != *previous {
+                *previous = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+//The size catches quick successive writes on file systems with coarse timestamps
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_file::TempFile;
+
+    #[test]
+    fn watcher_notices_changes() {
+        let file = TempFile::with_contents("watch", "frag", "void main() {}");
+        let path = file.path();
+        let mut watcher = ShaderWatcher::new(vec![path.to_owned()]);
+        assert!(!watcher.poll());
+
+        fs::write(path, "void main() { }").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(path).unwrap();
+        assert!(watcher.poll());
+    }
+}
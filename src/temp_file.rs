@@ -0,0 +1,34 @@
+//! Files written by unit tests, removed again when dropped so that failing tests do not leave them behind.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(crate) struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// `vulkan-tutorial-rs-<name>-<pid>.<extension>` in the temp directory, nothing is written yet. Tests running in
+    /// parallel need different names.
+    pub fn new(name: &str, extension: &str) -> Self {
+        let file_name = format!("vulkan-tutorial-rs-{}-{}.{}", name, std::process::id(), extension);
+        Self { path: std::env::temp_dir().join(file_name) }
+    }
+
+    pub fn with_contents<C: AsRef<[u8]>>(name: &str, extension: &str, contents: C) -> Self {
+        let file = Self::new(name, extension);
+        fs::write(&file.path, contents).unwrap();
+        file
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        //The test may have removed it already
+        let _ = fs::remove_file(&self.path);
+    }
+}
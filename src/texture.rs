@@ -0,0 +1,390 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use vulkano::format::Format;
+use vulkano::sampler::{Filter, MipmapMode, SamplerAddressMode, BorderColor};
+
+use crate::config::ConfigError;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const JPEG_SIGNATURE: [u8; 2] = [0xff, 0xd8];
+
+/// Decoded texture image, converted to RGBA8.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Texture {
+    /// A single pixel of `color`, sampled when a draw has no texture.
+    pub fn solid(color: [u8; 4]) -> Self {
+        Self { width: 1, height: 1, pixels: color.to_vec() }
+    }
+
+    /// Loads a PNG or JPEG image, recognized by its signature rather than its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let mut data = vec![];
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|e| TextureError::Io(path.to_owned(), e))?;
+
+        if data.starts_with(&PNG_SIGNATURE) {
+            decode_png(&data).map_err(|e| TextureError::Png(path.to_owned(), e))
+        } else if data.starts_with(&JPEG_SIGNATURE) {
+            decode_jpeg(&data).map_err(|e| TextureError::Jpeg(path.to_owned(), e))
+        } else {
+            Err(TextureError::UnsupportedFormat(path.to_owned()))
+        }
+    }
+
+    /// Number of levels in a full mip chain, down to 1x1.
+    pub fn mip_levels(&self) -> u32 {
+        32 - self.width.max(self.height).max(1).leading_zeros()
+    }
+
+    /// The next smaller mip level, each texel averaging a 2x2 block. sRGB colors are averaged in linear space.
+    pub fn downsample(&self, color_space: TextureColorSpace) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let texel = |x: u32, y: u32| {
+            let x = x.min(self.width - 1);
+            let y = y.min(self.height - 1);
+            let start = ((y * self.width + x) * 4) as usize;
+            &self.pixels[start..start + 4]
+        };
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let block = [texel(2 * x, 2 * y), texel(2 * x + 1, 2 * y), texel(2 * x, 2 * y + 1), texel(2 * x + 1, 2 * y + 1)];
+                for channel in 0..4 {
+                    //Alpha is always linear
+                    let srgb = color_space == TextureColorSpace::Srgb && channel < 3;
+                    let sum: f32 = block.iter()
+                        .map(|texel| if srgb { srgb_to_linear(texel[channel]) } else { f32::from(texel[channel]) / 255.0 })
+                        .sum();
+                    let average = sum / 4.0;
+                    let encoded = if srgb { linear_to_srgb(average) } else { average };
+                    pixels.push((encoded * 255.0).round() as u8);
+                }
+            }
+        }
+        Self { width, height, pixels }
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = f32::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn decode_png(data: &[u8]) -> Result<Texture, png::DecodingError> {
+    let mut decoder = png::Decoder::new(BufReader::new(data));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data)?;
+
+    let pixels = match info.color_type {
+        png::ColorType::Grayscale => data.iter().flat_map(|&l| vec![l, l, l, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::RGB => data.chunks_exact(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::RGBA => data,
+        png::ColorType::Indexed => return Err(png::DecodingError::Other("palette was not expanded".into())),
+    };
+    Ok(Texture { width: info.width, height: info.height, pixels })
+}
+
+fn decode_jpeg(data: &[u8]) -> Result<Texture, jpeg_decoder::Error> {
+    use jpeg_decoder::PixelFormat;
+
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(data));
+    let data = decoder.decode()?;
+    let info = decoder.info().unwrap();
+
+    let pixels = match info.pixel_format {
+        PixelFormat::L8 => data.iter().flat_map(|&l| vec![l, l, l, 255]).collect(),
+        PixelFormat::RGB24 => data.chunks_exact(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect(),
+        //Adobe JPEGs store inverted CMYK
+        PixelFormat::CMYK32 => data.chunks_exact(4)
+            .flat_map(|p| {
+                let k = u16::from(p[3]);
+                let channel = |c: u8| (u16::from(c) * k / 255) as u8;
+                vec![channel(p[0]), channel(p[1]), channel(p[2]), 255]
+            })
+            .collect(),
+    };
+    Ok(Texture { width: u32::from(info.width), height: u32::from(info.height), pixels })
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(PathBuf, io::Error),
+    Png(PathBuf, png::DecodingError),
+    Jpeg(PathBuf, jpeg_decoder::Error),
+    /// Neither a PNG nor a JPEG signature
+    UnsupportedFormat(PathBuf),
+}
+
+impl error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TextureError::Io(_, e) => Some(e),
+            TextureError::Png(_, e) => Some(e),
+            TextureError::Jpeg(_, e) => Some(e),
+            TextureError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Io(path, e) => write!(fmt, "{}: {}", path.display(), e),
+            TextureError::Png(path, e) => write!(fmt, "{}: invalid PNG: {}", path.display(), e),
+            TextureError::Jpeg(path, e) => write!(fmt, "{}: invalid JPEG: {}", path.display(), e),
+            TextureError::UnsupportedFormat(path) => write!(fmt, "{}: only PNG and JPEG images are supported", path.display()),
+        }
+    }
+}
+
+/// How texels are interpreted when sampled: colors are stored sRGB encoded, data such as normal maps is linear.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextureColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl TextureColorSpace {
+    pub fn format(self) -> Format {
+        match self {
+            TextureColorSpace::Srgb => Format::R8G8B8A8Srgb,
+            TextureColorSpace::Linear => Format::R8G8B8A8Unorm,
+        }
+    }
+}
+
+impl FromStr for TextureColorSpace {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srgb" => Ok(TextureColorSpace::Srgb),
+            "linear" => Ok(TextureColorSpace::Linear),
+            _ => Err(ConfigError::InvalidValue {
+                option: "texture-color-space",
+                reason: format!("unknown color space `{}`", s),
+            }),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+impl TextureFilter {
+    pub fn filter(self) -> Filter {
+        match self {
+            TextureFilter::Nearest => Filter::Nearest,
+            TextureFilter::Linear => Filter::Linear,
+        }
+    }
+
+    pub fn mipmap_mode(self) -> MipmapMode {
+        match self {
+            TextureFilter::Nearest => MipmapMode::Nearest,
+            TextureFilter::Linear => MipmapMode::Linear,
+        }
+    }
+}
+
+impl FromStr for TextureFilter {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(TextureFilter::Nearest),
+            "linear" => Ok(TextureFilter::Linear),
+            _ => Err(ConfigError::InvalidValue {
+                option: "texture-filter",
+                reason: format!("unknown filter `{}`", s),
+            }),
+        }
+    }
+}
+
+/// What sampling outside of the 0..1 texture coordinate range returns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextureAddressMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Transparent black
+    ClampToBorder,
+}
+
+impl TextureAddressMode {
+    pub fn address_mode(self) -> SamplerAddressMode {
+        match self {
+            TextureAddressMode::Repeat => SamplerAddressMode::Repeat,
+            TextureAddressMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+            TextureAddressMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
+            TextureAddressMode::ClampToBorder => SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+        }
+    }
+}
+
+impl FromStr for TextureAddressMode {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "repeat" => Ok(TextureAddressMode::Repeat),
+            "mirrored-repeat" => Ok(TextureAddressMode::MirroredRepeat),
+            "clamp-to-edge" => Ok(TextureAddressMode::ClampToEdge),
+            "clamp-to-border" => Ok(TextureAddressMode::ClampToBorder),
+            _ => Err(ConfigError::InvalidValue {
+                option: "texture-address-mode",
+                reason: format!("unknown address mode `{}`", s),
+            }),
+        }
+    }
+}
+
+/// Filtering, addressing and level of detail of the sampler textures are read through.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SamplerSettings {
+    pub mag_filter: TextureFilter,
+    pub min_filter: TextureFilter,
+    /// Filter between mip levels, linear gives trilinear filtering
+    pub mipmap_filter: TextureFilter,
+    /// Used for both texture coordinates
+    pub address_mode: TextureAddressMode,
+    /// Added to the mip level the hardware selects, positive values blur
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    /// Clamped to the texture's last mip level, 0 samples only the full resolution image
+    pub max_lod: f32,
+}
+
+impl SamplerSettings {
+    /// Vulkan's `VK_LOD_CLAMP_NONE`, every mip level is used.
+    pub const ALL_LEVELS: f32 = 1000.0;
+
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.min_lod >= 0.0 && self.min_lod <= self.max_lod) {
+            return Err(ConfigError::InvalidValue {
+                option: "texture-max-lod",
+                reason: format!("level of detail range {}..{} is empty or negative", self.min_lod, self.max_lod),
+            });
+        }
+        if !self.lod_bias.is_finite() {
+            return Err(ConfigError::InvalidValue {
+                option: "texture-lod-bias",
+                reason: format!("{} is not a finite number", self.lod_bias),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            mag_filter: TextureFilter::Linear,
+            min_filter: TextureFilter::Linear,
+            mipmap_filter: TextureFilter::Linear,
+            address_mode: TextureAddressMode::Repeat,
+            lod_bias: 0.0,
+            min_lod: 0.0,
+            max_lod: Self::ALL_LEVELS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_file::TempFile;
+
+    fn write_png(name: &str, color_type: png::ColorType, data: &[u8]) -> TempFile {
+        let file = TempFile::new(name, "png");
+        let mut encoder = png::Encoder::new(File::create(file.path()).unwrap(), 2, 1);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        file
+    }
+
+    #[test]
+    fn png_is_converted_to_rgba8() {
+        let file = write_png("rgb", png::ColorType::RGB, &[255, 0, 0, 0, 0, 255]);
+        let texture = Texture::load(file.path()).unwrap();
+
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.pixels, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn unknown_images_are_rejected() {
+        let file = TempFile::with_contents("unknown", "bmp", b"BM not really");
+        let result = Texture::load(file.path());
+        assert!(matches!(result, Err(TextureError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn color_space_picks_format() {
+        assert_eq!(TextureColorSpace::Srgb.format(), Format::R8G8B8A8Srgb);
+        assert_eq!("linear".parse::<TextureColorSpace>().unwrap().format(), Format::R8G8B8A8Unorm);
+    }
+
+    #[test]
+    fn downsampling_averages_blocks() {
+        let texture = Texture {
+            width: 3,
+            height: 2,
+            pixels: vec![
+                0, 0, 0, 0, 255, 255, 255, 255, 9, 9, 9, 9,
+                0, 0, 0, 0, 255, 255, 255, 255, 9, 9, 9, 9,
+            ],
+        };
+        assert_eq!(texture.mip_levels(), 2);
+
+        let linear = texture.downsample(TextureColorSpace::Linear);
+        assert_eq!((linear.width, linear.height), (1, 1));
+        assert_eq!(linear.pixels, vec![128, 128, 128, 128]);
+
+        //Half intensity in linear space is brighter once sRGB encoded, alpha stays linear
+        let srgb = texture.downsample(TextureColorSpace::Srgb);
+        assert_eq!(srgb.pixels, vec![188, 188, 188, 128]);
+        assert_eq!(srgb.downsample(TextureColorSpace::Srgb), srgb);
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use cgmath::{Deg, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+
+/// Maps OpenGL clip space, which cgmath's projections produce, to Vulkan's: Y points down and depth goes from 0 to 1
+const OPENGL_TO_VULKAN: Matrix4<f32> = Matrix4::new(
+    1.0,  0.0, 0.0, 0.0,
+    0.0, -1.0, 0.0, 0.0,
+    0.0,  0.0, 0.5, 0.0,
+    0.0,  0.0, 0.5, 1.0,
+);
+
+/// Degrees per second the model turns when spinning
+const SPIN_SPEED: f32 = 90.0;
+
+/// Matrices of the vertex shaders' `UniformBufferObject` block, uploaded every frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct Transforms {
+    pub model: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub projection: Matrix4<f32>,
+}
+
+impl Transforms {
+    /// Leaves positions as they are, so they are given directly in clip space.
+    pub fn identity() -> Self {
+        Self {
+            model: Matrix4::identity(),
+            view: Matrix4::identity(),
+            projection: Matrix4::identity(),
+        }
+    }
+
+    /// The model as it is, seen from `(2, 2, 2)` with Z up and a 45° field of view. The default for 3D layouts.
+    pub fn camera(aspect: f32) -> Self {
+        Self {
+            model: Matrix4::identity(),
+            view: Matrix4::look_at(Point3::new(2.0, 2.0, 2.0), Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+            projection: perspective(Deg(45.0), aspect, 0.1, 10.0),
+        }
+    }
+
+    /// The model turned around Z after `elapsed`, seen through `camera`.
+    pub fn spinning(elapsed: Duration, aspect: f32) -> Self {
+        let angle = Deg(elapsed.as_secs_f32() * SPIN_SPEED);
+        Self { model: Matrix4::from_angle_z(Rad::from(angle)), ..Self::camera(aspect) }
+    }
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Perspective projection into Vulkan's clip space.
+pub fn perspective(fovy: Deg<f32>, aspect: f32, near: f32, far: f32) -> Matrix4<f32> {
+    OPENGL_TO_VULKAN * cgmath::perspective(fovy, aspect, near, far)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Vector4, assert_relative_eq};
+
+    #[test]
+    fn perspective_uses_vulkan_depth_and_y() {
+        let projection = perspective(Deg(90.0), 1.0, 1.0, 10.0);
+
+        let near = projection * Vector4::new(0.0, 1.0, -1.0, 1.0);
+        assert_relative_eq!(near.z / near.w, 0.0);
+        assert_relative_eq!(near.y / near.w, -1.0);
+
+        let far = projection * Vector4::new(0.0, 0.0, -10.0, 1.0);
+        assert_relative_eq!(far.z / far.w, 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn camera_keeps_counter_clockwise_faces_in_front() {
+        let transforms = Transforms::camera(1.0);
+        let matrix = transforms.projection * transforms.view * transforms.model;
+        let clip = |point: [f32; 3]| {
+            let clip = matrix * Vector4::new(point[0], point[1], point[2], 1.0);
+            (clip.x / clip.w, clip.y / clip.w, clip.z / clip.w)
+        };
+
+        // The top face of a unit cube, counter-clockwise seen from above
+        let corners: Vec<_> = [[-0.5, -0.5, 0.5], [0.5, -0.5, 0.5], [0.5, 0.5, 0.5]].iter().map(|&p| clip(p)).collect();
+        assert!(corners.iter().all(|&(x, y, z)| x.abs() < 1.0 && y.abs() < 1.0 && 0.0 < z && z < 1.0));
+
+        // Vulkan judges the winding with Y pointing down, where counter-clockwise is a negative cross product
+        let area = (corners[1].0 - corners[0].0) * (corners[2].1 - corners[0].1)
+            - (corners[2].0 - corners[0].0) * (corners[1].1 - corners[0].1);
+        assert!(area < 0.0, "signed area {}", area);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use log::Level;
+use serde::Deserialize;
+use vulkano::instance::debug::{Message, MessageSeverity, MessageType};
+
+use crate::config::ConfigError;
+
+/// Current validation layer, shipped by SDKs since 1.1.106
+const KHRONOS_VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+/// Deprecated meta-layer, only found on older SDKs
+const LEGACY_VALIDATION_LAYER: &str = "VK_LAYER_LUNARG_standard_validation";
+
+/// Least severe validation message that gets reported.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationSeverity {
+    Verbose,
+    /// Also `info`, like on the command line
+    #[serde(alias = "info")]
+    Information,
+    Warning,
+    Error,
+}
+
+impl ValidationSeverity {
+    /// Severities the debug callback is registered for.
+    pub fn enabled(self) -> MessageSeverity {
+        MessageSeverity {
+            error: true,
+            warning: self <= ValidationSeverity::Warning,
+            information: self <= ValidationSeverity::Information,
+            verbose: self <= ValidationSeverity::Verbose,
+        }
+    }
+
+    /// The most severe flag set on a message.
+    pub fn of(severity: MessageSeverity) -> Self {
+        if severity.error {
+            ValidationSeverity::Error
+        } else if severity.warning {
+            ValidationSeverity::Warning
+        } else if severity.information {
+            ValidationSeverity::Information
+        } else {
+            ValidationSeverity::Verbose
+        }
+    }
+}
+
+impl FromStr for ValidationSeverity {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "verbose" => Ok(ValidationSeverity::Verbose),
+            "information" | "info" => Ok(ValidationSeverity::Information),
+            "warning" => Ok(ValidationSeverity::Warning),
+            "error" => Ok(ValidationSeverity::Error),
+            _ => Err(ConfigError::InvalidValue {
+                option: "validation-severity",
+                reason: format!("unknown severity `{}`", s),
+            }),
+        }
+    }
+}
+
+/// Which validation layer messages are reported and how often.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationSettings {
+    pub min_severity: ValidationSeverity,
+    pub general: bool,
+    pub validation: bool,
+    pub performance: bool,
+    /// Identical messages after this many are dropped, `0` reports all of them
+    pub max_repeats: u32,
+    /// Keep every reported message in the `ValidationLog` and fail frames on validation errors
+    pub strict: bool,
+}
+
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            min_severity: ValidationSeverity::Information,
+            general: true,
+            validation: true,
+            performance: true,
+            max_repeats: 10,
+            strict: false,
+        }
+    }
+}
+
+impl ValidationSettings {
+    pub fn message_types(&self) -> MessageType {
+        MessageType {
+            general: self.general,
+            validation: self.validation,
+            performance: self.performance,
+        }
+    }
+
+    /// Enables only the message types in a comma separated list such as `validation,performance`.
+    pub fn set_message_types(&mut self, list: &str) -> Result<(), ConfigError> {
+        let (mut general, mut validation, mut performance) = (false, false, false);
+        for ty in list.split(',').map(str::trim) {
+            match ty {
+                "general" => general = true,
+                "validation" => validation = true,
+                "performance" => performance = true,
+                _ => return Err(ConfigError::InvalidValue {
+                    option: "validation-types",
+                    reason: format!("unknown message type `{}`", ty),
+                }),
+            }
+        }
+
+        self.general = general;
+        self.validation = validation;
+        self.performance = performance;
+        Ok(())
+    }
+}
+
+/// Instance layers picked by `resolve_layers`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayerSelection {
+    /// Layers to enable, in the order they are passed to the instance
+    pub enabled: Vec<String>,
+    /// `None` if validation was not requested or no validation layer is installed
+    pub validation_layer: Option<String>,
+    /// Requested extra layers that are not installed
+    pub missing: Vec<String>,
+}
+
+/// Picks the validation layer, preferring the Khronos layer over the legacy meta-layer, followed by
+/// the installed `extra` layers.
+pub fn resolve_layers(available: &[String], validation: bool, extra: &[String]) -> LayerSelection {
+    let is_available = |name: &str| available.iter().any(|layer| layer == name);
+
+    let validation_layer = if validation {
+        [KHRONOS_VALIDATION_LAYER, LEGACY_VALIDATION_LAYER].iter()
+            .find(|layer| is_available(layer))
+            .map(|layer| layer.to_string())
+    } else {
+        None
+    };
+
+    let mut selection = LayerSelection {
+        enabled: validation_layer.iter().cloned().collect(),
+        validation_layer,
+        missing: vec![],
+    };
+    for layer in extra {
+        if !is_available(layer) {
+            selection.missing.push(layer.clone());
+        } else if !selection.enabled.contains(layer) {
+            selection.enabled.push(layer.clone());
+        }
+    }
+    selection
+}
+
+/// Log level a validation message is reported at.
+pub fn log_level(severity: MessageSeverity) -> Level {
+    match ValidationSeverity::of(severity) {
+        ValidationSeverity::Error => Level::Error,
+        ValidationSeverity::Warning => Level::Warn,
+        ValidationSeverity::Information => Level::Info,
+        ValidationSeverity::Verbose => Level::Debug,
+    }
+}
+
+/// Message types joined with `|`, e.g. `validation|performance`.
+pub fn type_names(ty: MessageType) -> String {
+    let names = [(ty.general, "general"), (ty.validation, "validation"), (ty.performance, "performance")];
+    names.iter()
+        .filter(|(set, _)| *set)
+        .map(|&(_, name)| name)
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Repeat {
+    Report,
+    /// Report this one and say that the following identical messages are suppressed
+    ReportLast,
+    Suppress,
+}
+
+/// Counts identical messages so a validation error raised every frame is only reported a few times.
+#[derive(Debug, Default)]
+pub struct RepeatLimiter {
+    max_repeats: u32,
+    counts: HashMap<String, u32>,
+}
+
+impl RepeatLimiter {
+    pub fn new(max_repeats: u32) -> Self {
+        Self { max_repeats, counts: HashMap::new() }
+    }
+
+    pub fn check(&mut self, description: &str) -> Repeat {
+        if self.max_repeats == 0 {
+            return Repeat::Report;
+        }
+
+        let count = self.counts.entry(description.to_owned()).or_insert(0);
+        *count = count.saturating_add(1);
+        if *count < self.max_repeats {
+            Repeat::Report
+        } else if *count == self.max_repeats {
+            Repeat::ReportLast
+        } else {
+            Repeat::Suppress
+        }
+    }
+}
+
+/// A message captured by the debug callback in strict mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationMessage {
+    pub severity: ValidationSeverity,
+    pub types: String,
+    pub layer_prefix: String,
+    pub description: String,
+}
+
+impl fmt::Display for ValidationMessage {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "[{}] {}: {}", self.layer_prefix, self.types, self.description)
+    }
+}
+
+#[derive(Debug, Default)]
+struct LogState {
+    messages: Vec<ValidationMessage>,
+    /// Number of messages already checked by `take_new_errors`
+    checked: usize,
+}
+
+/// Validation messages collected in strict mode, shared between the debug callback and the renderer.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationLog {
+    state: Arc<Mutex<LogState>>,
+}
+
+impl ValidationLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, message: ValidationMessage) {
+        if let Ok(mut state) = self.state.lock() {
+            state.messages.push(message);
+        }
+    }
+
+    pub fn messages(&self) -> Vec<ValidationMessage> {
+        self.state.lock().map(|state| state.messages.clone()).unwrap_or_default()
+    }
+
+    /// Captured messages at least as severe as `severity`.
+    pub fn messages_at_least(&self, severity: ValidationSeverity) -> Vec<ValidationMessage> {
+        self.messages().into_iter().filter(|message| message.severity >= severity).collect()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.messages.clear();
+            state.checked = 0;
+        }
+    }
+
+    /// Errors captured since the previous call.
+    pub fn take_new_errors(&self) -> Vec<ValidationMessage> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return vec![],
+        };
+        let errors = state.messages[state.checked..].iter()
+            .filter(|message| message.severity == ValidationSeverity::Error)
+            .cloned()
+            .collect();
+        state.checked = state.messages.len();
+        errors
+    }
+}
+
+/// Debug callback forwarding validation messages to the `log` crate, and to `validation_log` in strict mode.
+pub fn log_callback(settings: &ValidationSettings, validation_log: &ValidationLog) -> impl Fn(&Message) + Send + 'static {
+    let limiter = Mutex::new(RepeatLimiter::new(settings.max_repeats));
+    let validation_log = if settings.strict { Some(validation_log.clone()) } else { None };
+    move |msg: &Message| {
+        if let Some(validation_log) = &validation_log {
+            validation_log.push(ValidationMessage {
+                severity: ValidationSeverity::of(msg.severity),
+                types: type_names(msg.ty),
+                layer_prefix: msg.layer_prefix.to_owned(),
+                description: msg.description.to_owned(),
+            });
+        }
+
+        let repeat = limiter.lock()
+            .map(|mut limiter| limiter.check(msg.description))
+            .unwrap_or(Repeat::Report);
+        if repeat == Repeat::Suppress {
+            return;
+        }
+
+        let level = log_level(msg.severity);
+        log::log!(level, "[{}] {}: {}", msg.layer_prefix, type_names(msg.ty), msg.description);
+        if repeat == Repeat::ReportLast {
+            log::log!(level, "[{}] further identical messages will be suppressed", msg.layer_prefix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn severity(error: bool, warning: bool, information: bool, verbose: bool) -> MessageSeverity {
+        MessageSeverity { error, warning, information, verbose }
+    }
+
+    fn names(layers: &[&str]) -> Vec<String> {
+        layers.iter().map(|layer| layer.to_string()).collect()
+    }
+
+    #[test]
+    fn khronos_layer_is_preferred() {
+        let available = names(&[LEGACY_VALIDATION_LAYER, KHRONOS_VALIDATION_LAYER]);
+        let selection = resolve_layers(&available, true, &[]);
+        assert_eq!(selection.enabled, names(&[KHRONOS_VALIDATION_LAYER]));
+        assert_eq!(selection.validation_layer.as_deref(), Some(KHRONOS_VALIDATION_LAYER));
+    }
+
+    #[test]
+    fn legacy_layer_is_the_fallback() {
+        let selection = resolve_layers(&names(&[LEGACY_VALIDATION_LAYER]), true, &[]);
+        assert_eq!(selection.enabled, names(&[LEGACY_VALIDATION_LAYER]));
+        assert_eq!(resolve_layers(&[], true, &[]), LayerSelection::default());
+    }
+
+    #[test]
+    fn extra_layers_are_added_when_installed() {
+        let available = names(&[KHRONOS_VALIDATION_LAYER, "VK_LAYER_LUNARG_api_dump"]);
+        let extra = names(&["VK_LAYER_LUNARG_api_dump", "VK_LAYER_MESA_overlay", KHRONOS_VALIDATION_LAYER]);
+        let selection = resolve_layers(&available, false, &extra);
+        assert_eq!(selection.validation_layer, None);
+        assert_eq!(selection.enabled, names(&["VK_LAYER_LUNARG_api_dump", KHRONOS_VALIDATION_LAYER]));
+        assert_eq!(selection.missing, names(&["VK_LAYER_MESA_overlay"]));
+    }
+
+    #[test]
+    fn severities_map_to_log_levels() {
+        assert_eq!(log_level(severity(true, false, false, false)), Level::Error);
+        assert_eq!(log_level(severity(false, true, false, false)), Level::Warn);
+        assert_eq!(log_level(severity(false, false, true, false)), Level::Info);
+        assert_eq!(log_level(severity(false, false, false, true)), Level::Debug);
+    }
+
+    #[test]
+    fn min_severity_enables_more_severe_messages() {
+        assert_eq!(ValidationSeverity::Warning.enabled(), severity(true, true, false, false));
+        assert_eq!(ValidationSeverity::Verbose.enabled(), severity(true, true, true, true));
+        assert_eq!(ValidationSeverity::Error.enabled(), severity(true, false, false, false));
+    }
+
+    #[test]
+    fn severity_spellings_match_the_command_line() {
+        for &name in &["info", "information"] {
+            assert_eq!(name.parse::<ValidationSeverity>().unwrap(), ValidationSeverity::Information);
+            let settings: ValidationSettings = toml::from_str(&format!("min_severity = \"{}\"", name)).unwrap();
+            assert_eq!(settings.min_severity, ValidationSeverity::Information);
+        }
+    }
+
+    #[test]
+    fn message_types_parse_from_list() {
+        let mut settings = ValidationSettings::default();
+        settings.set_message_types("validation, performance").unwrap();
+        assert_eq!(type_names(settings.message_types()), "validation|performance");
+        assert!(settings.set_message_types("loader").is_err());
+    }
+
+    #[test]
+    fn identical_messages_are_rate_limited() {
+        let mut limiter = RepeatLimiter::new(2);
+        assert_eq!(limiter.check("a"), Repeat::Report);
+        assert_eq!(limiter.check("a"), Repeat::ReportLast);
+        assert_eq!(limiter.check("a"), Repeat::Suppress);
+        assert_eq!(limiter.check("b"), Repeat::Report);
+    }
+
+    fn message(severity: ValidationSeverity, description: &str) -> ValidationMessage {
+        ValidationMessage {
+            severity,
+            types: "validation".to_owned(),
+            layer_prefix: "Validation".to_owned(),
+            description: description.to_owned(),
+        }
+    }
+
+    #[test]
+    fn new_errors_are_taken_once() {
+        let log = ValidationLog::new();
+        log.push(message(ValidationSeverity::Warning, "slow"));
+        log.push(message(ValidationSeverity::Error, "broken"));
+        assert_eq!(log.take_new_errors(), vec![message(ValidationSeverity::Error, "broken")]);
+        assert!(log.take_new_errors().is_empty());
+
+        log.push(message(ValidationSeverity::Error, "broken again"));
+        assert_eq!(log.take_new_errors().len(), 1);
+        assert_eq!(log.messages_at_least(ValidationSeverity::Warning).len(), 3);
+        assert_eq!(log.messages_at_least(ValidationSeverity::Error).len(), 2);
+    }
+
+    #[test]
+    fn zero_max_repeats_reports_everything() {
+        let mut limiter = RepeatLimiter::new(0);
+        assert!((0..100).all(|_| limiter.check("a") == Repeat::Report));
+    }
+}
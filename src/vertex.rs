@@ -0,0 +1,235 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::config::ConfigError;
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub pos: [f32; 2],
+    pub color: [f32; 3],
+}
+
+impl Vertex {
+    pub fn new(pos: [f32; 2], color: [f32; 3]) -> Self {
+        Self { pos, color }
+    }
+}
+vulkano::impl_vertex!(Vertex, pos, color);
+
+pub fn vertices() -> [Vertex; 3] {
+    [
+        Vertex::new([0.0, -0.5], [1.0, 1.0, 1.0]),
+        Vertex::new([0.5, 0.5], [0.0, 1.0, 0.0]),
+        Vertex::new([-0.5, 0.5], [0.0, 0.0, 1.0])
+    ]
+}
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct ColorVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+vulkano::impl_vertex!(ColorVertex, position, color);
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct NormalColorVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 3],
+}
+vulkano::impl_vertex!(NormalColorVertex, position, normal, color);
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct NormalUvVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// Multiplied with the texture
+    pub color: [f32; 3],
+}
+vulkano::impl_vertex!(NormalUvVertex, position, normal, uv, color);
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct NormalUvTangentVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// `w` is the handedness of the bitangent, as in glTF
+    pub tangent: [f32; 4],
+    /// Multiplied with the texture
+    pub color: [f32; 3],
+}
+vulkano::impl_vertex!(NormalUvTangentVertex, position, normal, uv, tangent, color);
+
+/// Every attribute the model loaders know about, converted into one of the layouts before uploading.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    /// Zero when the model has no normals
+    pub normal: [f32; 3],
+    /// Zero when the model has no texture coordinates
+    pub uv: [f32; 2],
+    /// Zero when the model has no tangents
+    pub tangent: [f32; 4],
+    pub color: [f32; 3],
+}
+
+impl From<Vertex> for MeshVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self { position: [vertex.pos[0], vertex.pos[1], 0.0], color: vertex.color, ..Self::default() }
+    }
+}
+
+impl From<MeshVertex> for Vertex {
+    fn from(vertex: MeshVertex) -> Self {
+        Self::new([vertex.position[0], vertex.position[1]], vertex.color)
+    }
+}
+
+impl From<MeshVertex> for ColorVertex {
+    fn from(vertex: MeshVertex) -> Self {
+        Self { position: vertex.position, color: vertex.color }
+    }
+}
+
+impl From<MeshVertex> for NormalColorVertex {
+    fn from(vertex: MeshVertex) -> Self {
+        Self { position: vertex.position, normal: vertex.normal, color: vertex.color }
+    }
+}
+
+impl From<MeshVertex> for NormalUvVertex {
+    fn from(vertex: MeshVertex) -> Self {
+        Self { position: vertex.position, normal: vertex.normal, uv: vertex.uv, color: vertex.color }
+    }
+}
+
+impl From<MeshVertex> for NormalUvTangentVertex {
+    fn from(vertex: MeshVertex) -> Self {
+        Self {
+            position: vertex.position,
+            normal: vertex.normal,
+            uv: vertex.uv,
+            tangent: vertex.tangent,
+            color: vertex.color,
+        }
+    }
+}
+
+/// Vertex buffer layouts the graphics pipeline can be built for, each with its own vertex shader.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VertexLayout {
+    /// `Vertex`, the tutorial's 2D layout
+    #[default]
+    Position2Color,
+    /// `ColorVertex`
+    Position3Color,
+    /// `NormalColorVertex`
+    Position3NormalColor,
+    /// `NormalUvVertex`
+    Position3NormalUv,
+    /// `NormalUvTangentVertex`
+    Position3NormalUvTangent,
+}
+
+impl VertexLayout {
+    pub const ALL: [VertexLayout; 5] = [
+        VertexLayout::Position2Color,
+        VertexLayout::Position3Color,
+        VertexLayout::Position3NormalColor,
+        VertexLayout::Position3NormalUv,
+        VertexLayout::Position3NormalUvTangent,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VertexLayout::Position2Color => "position2-color",
+            VertexLayout::Position3Color => "position3-color",
+            VertexLayout::Position3NormalColor => "position3-normal-color",
+            VertexLayout::Position3NormalUv => "position3-normal-uv",
+            VertexLayout::Position3NormalUvTangent => "position3-normal-uv-tangent",
+        }
+    }
+
+    /// Layouts with texture coordinates are drawn with the fragment shader sampling a texture.
+    pub fn is_textured(self) -> bool {
+        matches!(self, VertexLayout::Position3NormalUv | VertexLayout::Position3NormalUvTangent)
+    }
+}
+
+impl fmt::Display for VertexLayout {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+impl FromStr for VertexLayout {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VertexLayout::ALL.iter()
+            .find(|layout| layout.name() == s)
+            .cloned()
+            .ok_or_else(|| ConfigError::InvalidValue {
+                option: "vertex-layout",
+                reason: format!("unknown vertex layout `{}`", s),
+            })
+    }
+}
+
+/// A vertex type registered with `impl_vertex!` that the pipeline can be built for.
+pub trait LayoutVertex: vulkano::pipeline::vertex::Vertex + From<MeshVertex> + Copy + Send + Sync + 'static {
+    const LAYOUT: VertexLayout;
+}
+
+impl LayoutVertex for Vertex {
+    const LAYOUT: VertexLayout = VertexLayout::Position2Color;
+}
+
+impl LayoutVertex for ColorVertex {
+    const LAYOUT: VertexLayout = VertexLayout::Position3Color;
+}
+
+impl LayoutVertex for NormalColorVertex {
+    const LAYOUT: VertexLayout = VertexLayout::Position3NormalColor;
+}
+
+impl LayoutVertex for NormalUvVertex {
+    const LAYOUT: VertexLayout = VertexLayout::Position3NormalUv;
+}
+
+impl LayoutVertex for NormalUvTangentVertex {
+    const LAYOUT: VertexLayout = VertexLayout::Position3NormalUvTangent;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_names_round_trip() {
+        for &layout in &VertexLayout::ALL {
+            assert_eq!(layout.name().parse::<VertexLayout>().unwrap(), layout);
+        }
+        assert!("position4".parse::<VertexLayout>().is_err());
+    }
+
+    #[test]
+    fn mesh_vertices_convert_to_layouts() {
+        let vertex = MeshVertex {
+            position: [1.0, 2.0, 3.0],
+            normal: [0.0, 0.0, 1.0],
+            uv: [0.5, 0.25],
+            tangent: [1.0, 0.0, 0.0, -1.0],
+            color: [1.0, 0.0, 0.0],
+        };
+        assert_eq!(Vertex::from(vertex), Vertex::new([1.0, 2.0], [1.0, 0.0, 0.0]));
+        assert_eq!(NormalUvTangentVertex::from(vertex).tangent, [1.0, 0.0, 0.0, -1.0]);
+        assert_eq!(NormalUvVertex::from(vertex).color, [1.0, 0.0, 0.0]);
+        assert_eq!(NormalUvTangentVertex::from(vertex).color, [1.0, 0.0, 0.0]);
+        assert_eq!(MeshVertex::from(Vertex::from(vertex)).position, [1.0, 2.0, 0.0]);
+    }
+}
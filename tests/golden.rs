@@ -0,0 +1,269 @@
+//! Golden-image regression tests: scenes are rendered offscreen, read back and compared
+//! against the reference PNGs checked in under `tests/golden`.
+//!
+//! Run with `GOLDEN_BLESS=1` to (re)write the references from the current output, a scene without a reference fails
+//! otherwise. Failed comparisons write `<name>.actual.png` and `<name>.diff.png` to `target/golden`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use cgmath::{Deg, Matrix4, Point3, SquareMatrix, Vector3};
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice};
+
+use vulkan_tutorial_rs::{
+    DrawList,
+    FrameCapture,
+    Mesh,
+    MeshVertex,
+    NormalColorVertex,
+    NormalUvVertex,
+    Renderer,
+    RendererConfig,
+    RendererError,
+    SamplerSettings,
+    Texture,
+    TextureColorSpace,
+    TextureFilter,
+    Transforms,
+    ValidationSettings,
+    ValidationSeverity,
+    perspective,
+};
+
+/// Per-channel tolerance absorbing rasterization differences between drivers
+const TOLERANCE: u8 = 2;
+
+/// Result of comparing two frames of identical dimensions.
+struct ImageDiff {
+    /// Number of pixels where at least one channel differs by more than the tolerance
+    mismatched_pixels: usize,
+    max_channel_delta: u8,
+    /// Mismatched pixels in red over a dimmed copy of the actual frame
+    diff: FrameCapture,
+}
+
+fn compare(expected: &FrameCapture, actual: &FrameCapture, tolerance: u8) -> ImageDiff {
+    assert_eq!((expected.width, expected.height), (actual.width, actual.height),
+        "frame dimensions differ from the reference");
+
+    let mut mismatched_pixels = 0;
+    let mut max_channel_delta = 0;
+    let mut diff_pixels = Vec::with_capacity(actual.pixels.len());
+
+    for (expected, actual) in expected.pixels.chunks_exact(4).zip(actual.pixels.chunks_exact(4)) {
+        let delta = expected.iter().zip(actual)
+            .map(|(e, a)| e.max(a) - e.min(a))
+            .max()
+            .unwrap_or(0);
+        max_channel_delta = max_channel_delta.max(delta);
+
+        if delta > tolerance {
+            mismatched_pixels += 1;
+            diff_pixels.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff_pixels.extend_from_slice(&[actual[0] / 4, actual[1] / 4, actual[2] / 4, 255]);
+        }
+    }
+
+    ImageDiff {
+        mismatched_pixels,
+        max_channel_delta,
+        diff: FrameCapture { width: actual.width, height: actual.height, pixels: diff_pixels },
+    }
+}
+
+/// Compares `actual` to `tests/golden/<name>.png`, panicking with a readable message on mismatch or when the
+/// reference has not been blessed yet.
+fn assert_matches_reference(name: &str, actual: &FrameCapture, tolerance: u8) {
+    let reference_path = reference_dir().join(format!("{}.png", name));
+
+    if env::var_os("GOLDEN_BLESS").is_some() {
+        fs::create_dir_all(reference_dir()).unwrap();
+        actual.save_png(&reference_path).unwrap();
+        println!("blessed {}", reference_path.display());
+        return;
+    }
+
+    if !reference_path.exists() {
+        fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.actual.png", name));
+        actual.save_png(&actual_path).unwrap();
+        panic!("{}: no reference image at {}, the output was written to {} (bless it with \
+            `GOLDEN_BLESS=1 cargo test --test golden`)", name, reference_path.display(), actual_path.display());
+    }
+    let expected = match FrameCapture::load_png(&reference_path) {
+        Ok(expected) => expected,
+        Err(e) => panic!("failed to load reference image {}: {}", reference_path.display(), e),
+    };
+
+    let diff = compare(&expected, actual, tolerance);
+    if diff.mismatched_pixels > 0 {
+        fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.actual.png", name));
+        let diff_path = output_dir().join(format!("{}.diff.png", name));
+        actual.save_png(&actual_path).unwrap();
+        diff.diff.save_png(&diff_path).unwrap();
+
+        panic!("{}: {} pixels differ from the reference by more than {} (max delta {}), see {} and {}",
+            name, diff.mismatched_pixels, tolerance, diff.max_channel_delta,
+            actual_path.display(), diff_path.display());
+    }
+}
+
+/// Golden tests are skipped on machines without any Vulkan implementation.
+fn vulkan_available() -> bool {
+    Instance::new(None, &InstanceExtensions::none(), None)
+        .map(|instance| PhysicalDevice::enumerate(&instance).next().is_some())
+        .unwrap_or(false)
+}
+
+fn reference_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden")
+}
+
+fn solid(width: u32, height: u32, rgba: [u8; 4]) -> FrameCapture {
+    let pixels = (0..width * height).flat_map(|_| rgba.to_vec()).collect();
+    FrameCapture { width, height, pixels }
+}
+
+#[test]
+fn compare_respects_tolerance() {
+    let expected = solid(4, 4, [100, 100, 100, 255]);
+    let actual = solid(4, 4, [102, 99, 100, 255]);
+
+    assert_eq!(compare(&expected, &actual, 2).mismatched_pixels, 0);
+    let diff = compare(&expected, &actual, 1);
+    assert_eq!(diff.mismatched_pixels, 16);
+    assert_eq!(diff.max_channel_delta, 2);
+}
+
+/// Renders the scene `setup` builds and compares it to its reference. Strict validation is used where a validation
+/// layer is installed, otherwise the scene is rendered unvalidated rather than skipped.
+fn check_scene<F>(name: &str, config: RendererConfig, setup: F)
+    where F: FnOnce(&mut Renderer)
+{
+    if !vulkan_available() {
+        println!("skipping golden test {}: no Vulkan device available", name);
+        return;
+    }
+
+    let validated = RendererConfig {
+        enable_validation_layers: true,
+        validation: ValidationSettings { strict: true, ..ValidationSettings::default() },
+        ..config.clone()
+    };
+    let mut renderer = match Renderer::initialize_headless(&validated) {
+        Err(RendererError::ValidationLayerUnavailable) => {
+            println!("rendering golden test {} without validation: no validation layer is installed", name);
+            let config = RendererConfig { enable_validation_layers: false, ..config };
+            Renderer::initialize_headless(&config).unwrap()
+        }
+        renderer => renderer.unwrap(),
+    };
+    setup(&mut renderer);
+    let frame = renderer.capture_frame().unwrap().expect("no frame was rendered");
+    assert_matches_reference(name, &frame, TOLERANCE);
+
+    let warnings = renderer.validation_log().messages_at_least(ValidationSeverity::Warning);
+    assert!(warnings.is_empty(), "validation warnings: {:#?}", warnings);
+}
+
+/// A unit cube with one color per face, wound counter-clockwise seen from outside.
+fn cube() -> Mesh<MeshVertex> {
+    let x: Vector3<f32> = Vector3::unit_x();
+    let y = Vector3::unit_y();
+    let z = Vector3::unit_z();
+    //Normal and two edges whose cross product is the normal
+    let faces = [(x, y, z), (-x, z, y), (y, z, x), (-y, x, z), (z, x, y), (-z, y, x)];
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for &(normal, u, v) in &faces {
+        let start = vertices.len() as u32;
+        let color = [normal.x.abs().max(0.2), normal.y.abs().max(0.2), normal.z.abs().max(0.2)];
+        for &(s, t) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = (normal + u * s + v * t) * 0.5;
+            vertices.push(MeshVertex {
+                position: position.into(),
+                normal: normal.into(),
+                color,
+                ..MeshVertex::default()
+            });
+        }
+        indices.extend_from_slice(&[start, start + 1, start + 2, start + 2, start + 3, start]);
+    }
+    Mesh::indexed(vertices, indices)
+}
+
+/// A unit square in the XY plane facing +Z, with texture coordinates covering it once.
+fn textured_quad() -> Mesh<MeshVertex> {
+    let corners = [
+        ([-0.5, -0.5], [0.0, 1.0]),
+        ([0.5, -0.5], [1.0, 1.0]),
+        ([0.5, 0.5], [1.0, 0.0]),
+        ([-0.5, 0.5], [0.0, 0.0]),
+    ];
+    let vertices = corners.iter()
+        .map(|&([x, y], uv)| MeshVertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv,
+            color: [1.0, 1.0, 1.0],
+            ..MeshVertex::default()
+        })
+        .collect();
+    Mesh::indexed(vertices, vec![0, 1, 2, 2, 3, 0])
+}
+
+/// 4x4 texels alternating between two colors.
+fn checkerboard() -> Texture {
+    let pixels = (0..16)
+        .flat_map(|i| if (i % 4 + i / 4) % 2 == 0 { vec![230, 120, 20, 255] } else { vec![20, 60, 200, 255] })
+        .collect();
+    Texture { width: 4, height: 4, pixels }
+}
+
+#[test]
+fn triangle() {
+    check_scene("triangle", RendererConfig::default(), |_| ());
+}
+
+#[test]
+fn indexed_quad() {
+    check_scene("indexed_quad", RendererConfig::default(), |renderer| {
+        renderer.set_mesh(&Mesh::quad()).unwrap();
+    });
+}
+
+#[test]
+fn cube_3d() {
+    check_scene("cube_3d", RendererConfig::default(), |renderer| {
+        renderer.set_mesh(&cube().convert::<NormalColorVertex>()).unwrap();
+        let transforms = Transforms::spinning(Duration::from_millis(250), renderer.aspect_ratio());
+        renderer.set_transforms(transforms);
+    });
+}
+
+#[test]
+fn textured_quad_3d() {
+    //Nearest filtering keeps the texel edges sharp, so drivers agree on them
+    let sampler = SamplerSettings { mag_filter: TextureFilter::Nearest, ..SamplerSettings::default() };
+    check_scene("textured_quad_3d", RendererConfig { sampler, ..RendererConfig::default() }, |renderer| {
+        let mesh = renderer.add_mesh(&textured_quad().convert::<NormalUvVertex>()).unwrap();
+        let texture = renderer.add_texture(&checkerboard(), TextureColorSpace::Srgb).unwrap();
+        let transforms = Transforms {
+            model: Matrix4::identity(),
+            view: Matrix4::look_at(Point3::new(0.0, 0.0, 1.5), Point3::new(0.0, 0.0, 0.0), Vector3::unit_y()),
+            projection: perspective(Deg(45.0), renderer.aspect_ratio(), 0.1, 10.0),
+        };
+        let mut draw_list = DrawList::new();
+        draw_list.push_textured(mesh, texture, transforms);
+        renderer.set_draw_list(draw_list).unwrap();
+    });
+}